@@ -0,0 +1,405 @@
+//! Read the dependency list that cargo-auditable embeds in a binary.
+//!
+//! The list is zlib compressed JSON in the `.dep-v0` section of an ELF file:
+//!
+//! ```json
+//! {"packages": [{"name": "foo", "version": "1.0.0", "source": "crates.io",
+//!   "dependencies": [1], "root": true}, ...]}
+//! ```
+//!
+//! It is turned into a `LockFile`, so that a report can be generated from a
+//! binary just as from its Cargo.lock.
+
+use crate::lockfile::{Dependency, LockFile, LockVersion, LockedPackage, Source};
+use serde_derive::Deserialize;
+use std::convert::TryFrom;
+use std::fmt;
+use std::io::{self, Read};
+
+pub const SECTION: &str = ".dep-v0";
+
+/// The most JSON we decompress, as a guard against zlib bombs.
+const MAX_JSON: u64 = 64 * 1024 * 1024;
+
+#[derive(Debug)]
+pub enum AuditableError {
+    NotElf,
+    /// The ELF headers point outside the file.
+    Truncated,
+    /// The binary wasn't built with cargo-auditable.
+    NoSection,
+    Decompress(io::Error),
+    Json(serde_json::Error),
+    /// A dependency index that isn't in the package list.
+    InvalidDependency(usize),
+}
+
+impl fmt::Display for AuditableError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AuditableError::NotElf => write!(f, "not an ELF file"),
+            AuditableError::Truncated => write!(f, "the ELF file is truncated"),
+            AuditableError::NoSection => write!(
+                f,
+                "there is no {} section, was it built with cargo auditable?",
+                SECTION
+            ),
+            AuditableError::Decompress(e) => {
+                write!(
+                    f,
+                    "the {} section could not be decompressed - {}",
+                    SECTION, e
+                )
+            }
+            AuditableError::Json(e) => {
+                write!(f, "the {} section could not be parsed - {}", SECTION, e)
+            }
+            AuditableError::InvalidDependency(i) => {
+                write!(f, "dependency {} is not in the package list", i)
+            }
+        }
+    }
+}
+
+impl std::error::Error for AuditableError {}
+
+/// Reads integers of the width and byte order of an ELF file.
+struct Elf<'a> {
+    data: &'a [u8],
+    is_64: bool,
+    big_endian: bool,
+}
+
+impl<'a> Elf<'a> {
+    fn uint(&self, offset: u64, size: u64) -> Result<u64, AuditableError> {
+        let bytes = self.bytes(offset, size)?;
+        let fold = |v: u64, b: &u8| (v << 8) | u64::from(*b);
+        Ok(if self.big_endian {
+            bytes.iter().fold(0, fold)
+        } else {
+            bytes.iter().rev().fold(0, fold)
+        })
+    }
+
+    /// A word, which is 4 bytes in a 32 bit file and 8 in a 64 bit one.
+    fn word(&self, offset: u64) -> Result<u64, AuditableError> {
+        self.uint(offset, if self.is_64 { 8 } else { 4 })
+    }
+
+    fn bytes(&self, offset: u64, size: u64) -> Result<&'a [u8], AuditableError> {
+        let start = usize::try_from(offset).map_err(|_| AuditableError::Truncated)?;
+        let size = usize::try_from(size).map_err(|_| AuditableError::Truncated)?;
+        self.data
+            .get(start..start.checked_add(size).ok_or(AuditableError::Truncated)?)
+            .ok_or(AuditableError::Truncated)
+    }
+}
+
+/// The contents of a named section of an ELF file.
+pub fn section<'a>(data: &'a [u8], name: &str) -> Result<&'a [u8], AuditableError> {
+    if data.len() < 6 || &data[..4] != b"\x7fELF" {
+        return Err(AuditableError::NotElf);
+    }
+    let elf = Elf {
+        data,
+        is_64: match data[4] {
+            1 => false,
+            2 => true,
+            _ => return Err(AuditableError::NotElf),
+        },
+        big_endian: match data[5] {
+            1 => false,
+            2 => true,
+            _ => return Err(AuditableError::NotElf),
+        },
+    };
+
+    // e_shoff, e_shentsize, e_shnum and e_shstrndx.
+    let (shoff, shentsize, shnum, shstrndx) = if elf.is_64 {
+        (
+            elf.word(0x28)?,
+            elf.uint(0x3a, 2)?,
+            elf.uint(0x3c, 2)?,
+            elf.uint(0x3e, 2)?,
+        )
+    } else {
+        (
+            elf.word(0x20)?,
+            elf.uint(0x2e, 2)?,
+            elf.uint(0x30, 2)?,
+            elf.uint(0x32, 2)?,
+        )
+    };
+
+    // sh_name, sh_type, sh_offset and sh_size of a section header. Offsets
+    // come from the file, so they may be anything.
+    let header = |i: u64| -> Result<(u64, u64, u64, u64), AuditableError> {
+        let at = i
+            .checked_mul(shentsize)
+            .and_then(|o| o.checked_add(shoff))
+            .ok_or(AuditableError::Truncated)?;
+        let field = |o: u64| at.checked_add(o).ok_or(AuditableError::Truncated);
+        if elf.is_64 {
+            Ok((
+                elf.uint(at, 4)?,
+                elf.uint(field(4)?, 4)?,
+                elf.word(field(24)?)?,
+                elf.word(field(32)?)?,
+            ))
+        } else {
+            Ok((
+                elf.uint(at, 4)?,
+                elf.uint(field(4)?, 4)?,
+                elf.word(field(16)?)?,
+                elf.word(field(20)?)?,
+            ))
+        }
+    };
+
+    let (_, _, names_offset, names_size) = header(shstrndx)?;
+    let names = elf.bytes(names_offset, names_size)?;
+    for i in 0..shnum {
+        let (name_at, kind, offset, size) = header(i)?;
+        let section_name = names
+            .get(name_at as usize..)
+            .and_then(|n| n.split(|&b| b == 0).next())
+            .unwrap_or_default();
+        // SHT_NOBITS sections take no space in the file.
+        if section_name == name.as_bytes() && kind != 8 {
+            return elf.bytes(offset, size);
+        }
+    }
+    Err(AuditableError::NoSection)
+}
+
+/// Does an ELF file look like it was built by rustc? Panic messages embed
+/// the path of the standard library sources, `/rustc/<commit>/library/...`.
+pub fn is_rust(data: &[u8]) -> bool {
+    section(data, ".comment").is_ok() && data.windows(7).any(|w| w == b"/rustc/")
+}
+
+#[derive(Deserialize)]
+struct Info {
+    packages: Vec<Package>,
+}
+
+#[derive(Deserialize)]
+struct Package {
+    name: String,
+    version: String,
+    source: String,
+    #[serde(default)]
+    dependencies: Vec<usize>,
+    /// "build" for build scripts and proc-macros, which aren't linked into
+    /// the binary, otherwise "runtime".
+    #[serde(default)]
+    kind: Option<String>,
+}
+
+impl Package {
+    fn is_linked(&self) -> bool {
+        self.kind.as_deref() != Some("build")
+    }
+}
+
+/// The source a package would have in Cargo.lock. cargo-auditable only
+/// records the kind of source, not the git or registry URL.
+fn lock_source(source: &str) -> Option<String> {
+    match source {
+        "local" => None,
+        "crates.io" => Some("registry+https://github.com/rust-lang/crates.io-index".to_string()),
+        "git" => Some("git+".to_string()),
+        other => Some(other.to_string()),
+    }
+}
+
+/// Read the dependencies embedded in an ELF binary. Build dependencies are
+/// left out, as nothing of them ends up in the binary.
+pub fn read(data: &[u8]) -> Result<LockFile, AuditableError> {
+    let compressed = section(data, SECTION)?;
+    let mut json = Vec::new();
+    flate2::read::ZlibDecoder::new(compressed)
+        .take(MAX_JSON)
+        .read_to_end(&mut json)
+        .map_err(AuditableError::Decompress)?;
+    let info: Info = serde_json::from_slice(&json).map_err(AuditableError::Json)?;
+
+    let packages = info
+        .packages
+        .iter()
+        .filter(|pkg| pkg.is_linked())
+        .map(|pkg| {
+            let mut dependencies = Vec::new();
+            for &i in &pkg.dependencies {
+                let dep = info
+                    .packages
+                    .get(i)
+                    .ok_or(AuditableError::InvalidDependency(i))?;
+                if dep.is_linked() {
+                    dependencies.push(Dependency {
+                        name: dep.name.clone(),
+                        version: Some(dep.version.clone()),
+                        source: lock_source(&dep.source),
+                    });
+                }
+            }
+            let raw_source = lock_source(&pkg.source);
+            Ok(LockedPackage {
+                name: pkg.name.clone(),
+                version: pkg.version.clone(),
+                source: raw_source.as_deref().map(Source::parse),
+                raw_source,
+                checksum: None,
+                dependencies,
+            })
+        })
+        .collect::<Result<Vec<_>, AuditableError>>()?;
+
+    Ok(LockFile {
+        version: LockVersion::V4,
+        packages,
+    })
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::io::Write;
+
+    /// A little endian ELF64 file holding just the given sections.
+    pub(crate) fn elf(sections: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut names = vec![0u8];
+        let mut data = Vec::new();
+        // (name offset, type, file offset, size), starting with the null
+        // section and ending with the section names.
+        let mut headers = vec![(0, 0, 0, 0)];
+        for (name, contents) in sections {
+            headers.push((names.len(), 1, 64 + data.len(), contents.len()));
+            names.extend_from_slice(name.as_bytes());
+            names.push(0);
+            data.extend_from_slice(contents);
+        }
+        let shstrtab = names.len();
+        names.extend_from_slice(b".shstrtab\0");
+        headers.push((shstrtab, 3, 64 + data.len(), names.len()));
+        data.extend_from_slice(&names);
+
+        let mut out = vec![0u8; 64];
+        out[..6].copy_from_slice(b"\x7fELF\x02\x01");
+        let shoff = 64 + data.len() as u64;
+        out[0x28..0x30].copy_from_slice(&shoff.to_le_bytes());
+        out[0x3a..0x3c].copy_from_slice(&64u16.to_le_bytes());
+        out[0x3c..0x3e].copy_from_slice(&(headers.len() as u16).to_le_bytes());
+        out[0x3e..0x40].copy_from_slice(&(headers.len() as u16 - 1).to_le_bytes());
+        out.extend_from_slice(&data);
+        for (name, kind, offset, size) in headers {
+            let mut header = [0u8; 64];
+            header[0..4].copy_from_slice(&(name as u32).to_le_bytes());
+            header[4..8].copy_from_slice(&(kind as u32).to_le_bytes());
+            header[24..32].copy_from_slice(&(offset as u64).to_le_bytes());
+            header[32..40].copy_from_slice(&(size as u64).to_le_bytes());
+            out.extend_from_slice(&header);
+        }
+        out
+    }
+
+    pub(crate) fn zlib(data: &[u8]) -> Vec<u8> {
+        let mut encoder =
+            flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    pub(crate) const JSON: &str = r#"{"packages": [
+        {"name": "app", "version": "0.1.0", "source": "local", "dependencies": [1, 2], "root": true},
+        {"name": "itoa", "version": "1.0.11", "source": "crates.io"},
+        {"name": "cc", "version": "1.0.0", "source": "crates.io", "kind": "build"}
+    ]}"#;
+
+    #[test]
+    fn sections() {
+        let data = elf(&[(".text", b"code"), (".comment", b"rustc")]);
+        assert_eq!(section(&data, ".comment").unwrap(), b"rustc");
+        assert!(matches!(
+            section(&data, SECTION),
+            Err(AuditableError::NoSection)
+        ));
+        assert!(matches!(read(&data), Err(AuditableError::NoSection)));
+    }
+
+    #[test]
+    fn not_elf() {
+        for data in [&b""[..], b"\x7fEL", b"#!/bin/sh\n", b"\x7fELF\x03\x01"].iter() {
+            assert!(matches!(
+                section(data, SECTION),
+                Err(AuditableError::NotElf)
+            ));
+        }
+    }
+
+    #[test]
+    fn truncated() {
+        let data = elf(&[(SECTION, &zlib(JSON.as_bytes()))]);
+        // Cut off in the ELF header, and in the section headers.
+        for len in [6, 0x30, data.len() - 30].iter() {
+            assert!(
+                matches!(read(&data[..*len]), Err(AuditableError::Truncated)),
+                "{}",
+                len
+            );
+        }
+
+        // A section that claims to run past the end of the file.
+        let mut data = data;
+        let size_at = data.len() - 64 * 2 + 32;
+        data[size_at..size_at + 8].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert!(matches!(read(&data), Err(AuditableError::Truncated)));
+    }
+
+    #[test]
+    fn header_offsets_overflow() {
+        // Section headers so close to u64::MAX that their fields wrap.
+        let mut data = elf(&[(SECTION, &zlib(JSON.as_bytes()))]);
+        for shoff in [u64::MAX, u64::MAX - 20, u64::MAX - 64 * 2].iter() {
+            data[0x28..0x30].copy_from_slice(&shoff.to_le_bytes());
+            assert!(
+                matches!(read(&data), Err(AuditableError::Truncated)),
+                "{}",
+                shoff
+            );
+        }
+    }
+
+    #[test]
+    fn bad_contents() {
+        let data = elf(&[(SECTION, b"not zlib")]);
+        assert!(matches!(read(&data), Err(AuditableError::Decompress(_))));
+
+        let data = elf(&[(SECTION, &zlib(b"{\"packages\": 1}"))]);
+        assert!(matches!(read(&data), Err(AuditableError::Json(_))));
+
+        let json = r#"{"packages": [{"name": "a", "version": "1.0.0", "source": "local", "dependencies": [7]}]}"#;
+        let data = elf(&[(SECTION, &zlib(json.as_bytes()))]);
+        assert!(matches!(
+            read(&data),
+            Err(AuditableError::InvalidDependency(7))
+        ));
+    }
+
+    #[test]
+    fn build_dependencies_are_not_linked() {
+        let data = elf(&[(".text", b"code"), (SECTION, &zlib(JSON.as_bytes()))]);
+        let lock = read(&data).unwrap();
+        let names: Vec<&str> = lock.packages.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, ["app", "itoa"]);
+
+        let app = &lock.packages[0];
+        assert_eq!(app.raw_source, None);
+        assert_eq!(app.dependencies.len(), 1);
+        assert_eq!(lock.resolve(&app.dependencies[0]).unwrap().name, "itoa");
+        assert_eq!(
+            lock.packages[1].raw_source.as_deref(),
+            Some("registry+https://github.com/rust-lang/crates.io-index")
+        );
+    }
+}
@@ -0,0 +1,434 @@
+//! Changelog entries describing how the bundled crates changed between two
+//! lockfiles.
+
+use crate::license::LicenseExpr;
+use crate::lockfile::{LockFile, SourceKind};
+use crate::rpmver;
+use crate::sbom::civil_from_days;
+use crate::vendored::VendoredCrate;
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::env;
+use std::fmt;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Change {
+    Added {
+        name: String,
+        version: String,
+        license: Option<LicenseExpr>,
+    },
+    Removed {
+        name: String,
+        version: String,
+    },
+    Upgraded {
+        name: String,
+        from: String,
+        to: String,
+    },
+    Downgraded {
+        name: String,
+        from: String,
+        to: String,
+    },
+    License {
+        name: String,
+        version: String,
+        from: Option<LicenseExpr>,
+        to: Option<LicenseExpr>,
+    },
+}
+
+fn or_unknown(license: &Option<LicenseExpr>) -> String {
+    license
+        .as_ref()
+        .map(|l| l.to_string())
+        .unwrap_or_else(|| "an unknown license".to_string())
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Change::Added {
+                name,
+                version,
+                license: Some(license),
+            } => write!(f, "Add {} {} ({})", name, version, license),
+            Change::Added { name, version, .. } => write!(f, "Add {} {}", name, version),
+            Change::Removed { name, version } => write!(f, "Remove {} {}", name, version),
+            Change::Upgraded { name, from, to } => {
+                write!(f, "Update {} from {} to {}", name, from, to)
+            }
+            Change::Downgraded { name, from, to } => {
+                write!(f, "Downgrade {} from {} to {}", name, from, to)
+            }
+            Change::License {
+                name,
+                version,
+                from,
+                to,
+            } => write!(
+                f,
+                "License of {} {} changed from {} to {}",
+                name,
+                version,
+                or_unknown(from),
+                or_unknown(to)
+            ),
+        }
+    }
+}
+
+fn find<'a>(crates: &'a [VendoredCrate], name: &str, version: &str) -> Option<&'a VendoredCrate> {
+    crates
+        .iter()
+        .find(|c| c.name == name && c.version == version)
+}
+
+/// The bundled versions of each crate in a lockfile.
+fn versions(lock: &LockFile) -> BTreeMap<&str, Vec<&str>> {
+    let mut versions: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+    for pkg in &lock.packages {
+        if pkg.kind() != SourceKind::Local {
+            versions.entry(&pkg.name).or_default().push(&pkg.version);
+        }
+    }
+    for v in versions.values_mut() {
+        v.sort_by(|a, b| rpmver::rpmvercmp(&rpmver::from_semver(a), &rpmver::from_semver(b)));
+        v.dedup();
+    }
+    versions
+}
+
+/// Compare the bundled crates of two lockfiles. The licenses come from the
+/// crates vendored for each, and are only compared when both crates were
+/// found, whether or not the version changed.
+pub fn compare(
+    old: &LockFile,
+    new: &LockFile,
+    old_crates: &[VendoredCrate],
+    new_crates: &[VendoredCrate],
+) -> Vec<Change> {
+    let old_versions = versions(old);
+    let new_versions = versions(new);
+    let mut names: Vec<&str> = old_versions
+        .keys()
+        .chain(new_versions.keys())
+        .copied()
+        .collect();
+    names.sort_unstable();
+    names.dedup();
+
+    // The license change between two versions of a crate, if any.
+    let license_change = |name: &str, from: &str, to: &str| {
+        let old_license = find(old_crates, name, from).map(|c| c.license.clone());
+        let new_license = find(new_crates, name, to).map(|c| c.license.clone());
+        match (old_license, new_license) {
+            (Some(old_license), Some(new_license)) if old_license != new_license => {
+                Some(Change::License {
+                    name: name.to_string(),
+                    version: to.to_string(),
+                    from: old_license,
+                    to: new_license,
+                })
+            }
+            _ => None,
+        }
+    };
+
+    let mut changes = Vec::new();
+    for name in names {
+        let before = old_versions.get(name).cloned().unwrap_or_default();
+        let after = new_versions.get(name).cloned().unwrap_or_default();
+        let removed: Vec<&str> = before
+            .iter()
+            .filter(|v| !after.contains(v))
+            .copied()
+            .collect();
+        let added: Vec<&str> = after
+            .iter()
+            .filter(|v| !before.contains(v))
+            .copied()
+            .collect();
+
+        for version in before.iter().filter(|v| after.contains(v)) {
+            changes.extend(license_change(name, version, version));
+        }
+
+        // Pair up the old and new versions, oldest first, so that a crate
+        // bumped from 1.0 to 1.1 is one update rather than a removal and an
+        // addition. Any left over were added or removed.
+        for (from, to) in removed.iter().zip(added.iter()) {
+            let ord = rpmver::rpmvercmp(&rpmver::from_semver(from), &rpmver::from_semver(to));
+            let license = license_change(name, from, to);
+            let (name, from, to) = (name.to_string(), from.to_string(), to.to_string());
+            changes.push(if ord == Ordering::Greater {
+                Change::Downgraded { name, from, to }
+            } else {
+                Change::Upgraded { name, from, to }
+            });
+            changes.extend(license);
+        }
+        for version in removed.iter().skip(added.len()) {
+            changes.push(Change::Removed {
+                name: name.to_string(),
+                version: version.to_string(),
+            });
+        }
+        for version in added.iter().skip(removed.len()) {
+            changes.push(Change::Added {
+                name: name.to_string(),
+                version: version.to_string(),
+                license: find(new_crates, name, version).and_then(|c| c.license.clone()),
+            });
+        }
+    }
+    changes
+}
+
+/// A bullet list for an openSUSE `.changes` entry, or None if nothing
+/// changed.
+pub fn changes_entry(changes: &[Change]) -> Option<String> {
+    if changes.is_empty() {
+        return None;
+    }
+    let mut out = String::from("- Update vendored dependencies:\n");
+    for change in changes {
+        out.push_str(&format!("  * {}\n", change));
+    }
+    Some(out)
+}
+
+/// An RPM `%changelog` entry, dated and signed by the packager, or None if
+/// nothing changed.
+pub fn rpm_changelog(changes: &[Change], date: SystemTime, packager: &str) -> Option<String> {
+    if changes.is_empty() {
+        return None;
+    }
+    const DAYS: [&str; 7] = ["Thu", "Fri", "Sat", "Sun", "Mon", "Tue", "Wed"];
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+    let days = date
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs() as i64
+        / 86400;
+    let (year, month, day) = civil_from_days(days);
+
+    let mut out = format!(
+        "* {} {} {:02} {} {}\n- Update vendored dependencies\n",
+        DAYS[days.rem_euclid(7) as usize],
+        MONTHS[month as usize - 1],
+        day,
+        year,
+        packager
+    );
+    for change in changes {
+        out.push_str(&format!("- {}\n", change));
+    }
+    Some(out)
+}
+
+/// The value of a `key = value` line in an ini style file.
+fn ini_value(text: &str, key: &str) -> Option<String> {
+    text.lines().find_map(|line| {
+        let (k, v) = line.split_once('=')?;
+        Some(v.trim().to_string()).filter(|v| k.trim() == key && !v.is_empty())
+    })
+}
+
+/// The `%packager` defined in an rpmmacros file.
+fn macros_packager(text: &str) -> Option<String> {
+    text.lines().find_map(|line| {
+        let value = line.trim().strip_prefix("%packager")?.trim();
+        Some(value.to_string()).filter(|v| !v.is_empty())
+    })
+}
+
+/// The `realname` and `email` of an osc config.
+fn osc_packager(text: &str) -> Option<String> {
+    let email = ini_value(text, "email")?;
+    Some(match ini_value(text, "realname") {
+        Some(name) => format!("{} <{}>", name, email),
+        None => format!("<{}>", email),
+    })
+}
+
+/// Who signs a %changelog entry: $RPM_PACKAGER, %packager from ~/.rpmmacros,
+/// the realname and email of the osc config, or $MAILADDR as used by
+/// `osc vc`.
+pub fn packager() -> Option<String> {
+    if let Some(packager) = env::var("RPM_PACKAGER").ok().filter(|p| !p.is_empty()) {
+        return Some(packager);
+    }
+    let home = env::var_os("HOME").map(PathBuf::from);
+
+    let rpmmacros = home
+        .as_ref()
+        .and_then(|h| std::fs::read_to_string(h.join(".rpmmacros")).ok());
+    if let Some(packager) = rpmmacros.as_deref().and_then(macros_packager) {
+        return Some(packager);
+    }
+
+    let config = env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| home.as_ref().map(|h| h.join(".config")));
+    let oscrc = config
+        .map(|c| c.join("osc/oscrc"))
+        .into_iter()
+        .chain(home.map(|h| h.join(".oscrc")))
+        .find_map(|path| std::fs::read_to_string(path).ok());
+    if let Some(packager) = oscrc.as_deref().and_then(osc_packager) {
+        return Some(packager);
+    }
+
+    env::var("MAILADDR").ok().filter(|m| !m.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn changes() -> Vec<Change> {
+        vec![
+            Change::Upgraded {
+                name: "itoa".to_string(),
+                from: "1.0.10".to_string(),
+                to: "1.0.11".to_string(),
+            },
+            Change::Removed {
+                name: "cc".to_string(),
+                version: "1.0.0".to_string(),
+            },
+        ]
+    }
+
+    #[test]
+    fn nothing_changed() {
+        assert_eq!(changes_entry(&[]), None);
+        assert_eq!(rpm_changelog(&[], UNIX_EPOCH, "A <a@example.com>"), None);
+    }
+
+    #[test]
+    fn entries() {
+        assert_eq!(
+            changes_entry(&changes()).unwrap(),
+            "- Update vendored dependencies:\n  * Update itoa from 1.0.10 to 1.0.11\n  * Remove cc 1.0.0\n"
+        );
+        let date = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        assert_eq!(
+            rpm_changelog(&changes(), date, "A <a@example.com>").unwrap(),
+            "* Tue Nov 14 2023 A <a@example.com>\n- Update vendored dependencies\n\
+             - Update itoa from 1.0.10 to 1.0.11\n- Remove cc 1.0.0\n"
+        );
+    }
+
+    fn lock(packages: &[(&str, &str)]) -> LockFile {
+        let mut text =
+            String::from("version = 4\n\n[[package]]\nname = \"app\"\nversion = \"0.1.0\"\n");
+        for (name, version) in packages {
+            text.push_str(&format!(
+                "\n[[package]]\nname = \"{}\"\nversion = \"{}\"\n\
+                 source = \"registry+https://github.com/rust-lang/crates.io-index\"\n",
+                name, version
+            ));
+        }
+        LockFile::parse(text.as_bytes()).unwrap()
+    }
+
+    fn krate(name: &str, version: &str, license: &str) -> VendoredCrate {
+        VendoredCrate {
+            name: name.to_string(),
+            version: version.to_string(),
+            dir: format!("{}-{}", name, version).into(),
+            license: Some(LicenseExpr::parse(license).unwrap()),
+            license_source: crate::vendored::LicenseSource::Declared,
+        }
+    }
+
+    fn described(changes: Vec<Change>) -> Vec<String> {
+        changes.iter().map(|c| c.to_string()).collect()
+    }
+
+    #[test]
+    fn versions_changed() {
+        let old = lock(&[("cc", "1.0.0"), ("itoa", "1.0.10"), ("libc", "0.2.150")]);
+        let new = lock(&[("itoa", "1.0.11"), ("libc", "0.2.149"), ("ryu", "1.0.18")]);
+        let new_crates = [krate("ryu", "1.0.18", "Apache-2.0 OR BSL-1.0")];
+        assert_eq!(
+            described(compare(&old, &new, &[], &new_crates)),
+            [
+                "Remove cc 1.0.0",
+                "Update itoa from 1.0.10 to 1.0.11",
+                "Downgrade libc from 0.2.150 to 0.2.149",
+                "Add ryu 1.0.18 (Apache-2.0 OR BSL-1.0)",
+            ]
+        );
+        assert!(compare(&old, &old, &[], &[]).is_empty());
+    }
+
+    #[test]
+    fn multiple_locked_versions() {
+        // The oldest removed version pairs with the oldest added one.
+        let old = lock(&[("syn", "1.0.109"), ("syn", "2.0.50")]);
+        let new = lock(&[("syn", "1.0.109"), ("syn", "2.0.60"), ("syn", "3.0.0")]);
+        assert_eq!(
+            described(compare(&old, &new, &[], &[])),
+            ["Update syn from 2.0.50 to 2.0.60", "Add syn 3.0.0"]
+        );
+        assert_eq!(
+            described(compare(&new, &old, &[], &[])),
+            ["Downgrade syn from 2.0.60 to 2.0.50", "Remove syn 3.0.0"]
+        );
+    }
+
+    #[test]
+    fn licenses_changed() {
+        let old = lock(&[("itoa", "1.0.10"), ("ryu", "1.0.18"), ("cc", "1.0.0")]);
+        let new = lock(&[("itoa", "1.0.11"), ("ryu", "1.0.18"), ("cc", "1.0.0")]);
+        let old_crates = [
+            krate("itoa", "1.0.10", "MIT"),
+            krate("ryu", "1.0.18", "Apache-2.0"),
+            krate("cc", "1.0.0", "MIT"),
+        ];
+        // cc wasn't vendored this time, so its license can't be compared.
+        let new_crates = [
+            krate("itoa", "1.0.11", "MIT OR Apache-2.0"),
+            krate("ryu", "1.0.18", "Apache-2.0 OR BSL-1.0"),
+        ];
+        assert_eq!(
+            described(compare(&old, &new, &old_crates, &new_crates)),
+            [
+                "Update itoa from 1.0.10 to 1.0.11",
+                "License of itoa 1.0.11 changed from MIT to Apache-2.0 OR MIT",
+                "License of ryu 1.0.18 changed from Apache-2.0 to Apache-2.0 OR BSL-1.0",
+            ]
+        );
+    }
+
+    #[test]
+    fn packager_from_config_files() {
+        assert_eq!(
+            macros_packager("%_topdir /tmp\n%packager  Jane Doe <jane@example.com>\n").as_deref(),
+            Some("Jane Doe <jane@example.com>")
+        );
+        assert_eq!(macros_packager("%packager\n"), None);
+
+        let oscrc = "[general]\napiurl = https://api.opensuse.org\n\n\
+                     [https://api.opensuse.org]\nemail = jane@example.com\nrealname = Jane Doe\n";
+        assert_eq!(
+            osc_packager(oscrc).as_deref(),
+            Some("Jane Doe <jane@example.com>")
+        );
+        assert_eq!(
+            osc_packager("email=jane@example.com\nrealname =\n").as_deref(),
+            Some("<jane@example.com>")
+        );
+        assert_eq!(osc_packager("realname = Jane Doe\n"), None);
+    }
+}
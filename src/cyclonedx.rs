@@ -0,0 +1,414 @@
+//! CycloneDX 1.5 SBOMs, in the JSON and XML formats.
+
+use crate::license::LicenseExpr;
+use crate::lockfile::Source;
+use crate::sbom::{spdx_expression, Component, Sbom};
+use crate::spdx;
+use serde_json::{json, Value};
+use std::fmt::{self, Write};
+
+/// How a license is written in CycloneDX. A single license on the SPDX list
+/// is an id, a single license that isn't is a name, and anything else is an
+/// expression.
+enum License {
+    Id(&'static str),
+    Name(String),
+    Expression(String),
+}
+
+impl License {
+    fn new(expr: &LicenseExpr) -> Self {
+        match expr {
+            LicenseExpr::License {
+                id,
+                plus: false,
+                exception: None,
+            } => match spdx::license(id) {
+                Some((id, _)) => License::Id(id),
+                None if spdx::is_license_ref(id) => License::Expression(id.clone()),
+                None => License::Name(id.clone()),
+            },
+            _ => License::Expression(spdx_expression(expr)),
+        }
+    }
+}
+
+/// The external reference for where a component came from.
+fn reference<'a>(c: &'a Component) -> Option<(&'static str, &'a str)> {
+    let kind = match c.package.source {
+        Some(Source::Git { .. }) => "vcs",
+        _ => "distribution",
+    };
+    c.download.as_deref().map(|url| (kind, url))
+}
+
+fn component_type(sbom: &Sbom, i: usize) -> &'static str {
+    if sbom.roots.contains(&i) {
+        "application"
+    } else {
+        "library"
+    }
+}
+
+/// A CycloneDX 1.5 document in the JSON format.
+pub fn json(sbom: &Sbom) -> String {
+    let component = |i: usize| {
+        let c = &sbom.components[i];
+        let mut value = json!({
+            "type": component_type(sbom, i),
+            "bom-ref": c.key,
+            "name": c.package.name,
+            "version": c.package.version,
+            "purl": c.purl,
+        });
+        if let Some(sum) = c.sha256 {
+            value["hashes"] = json!([{ "alg": "SHA-256", "content": sum }]);
+        }
+        if let Some(expr) = &c.concluded {
+            value["licenses"] = match License::new(expr) {
+                License::Id(id) => json!([{ "license": { "id": id } }]),
+                License::Name(name) => json!([{ "license": { "name": name } }]),
+                License::Expression(expr) => json!([{ "expression": expr }]),
+            };
+        }
+        if let Some((kind, url)) = reference(c) {
+            value["externalReferences"] = json!([{ "type": kind, "url": url }]);
+        }
+        value
+    };
+
+    // The first workspace member is the subject of the document, the rest
+    // are components like any other.
+    let root = sbom.roots.first().copied();
+    let components: Vec<Value> = (0..sbom.components.len())
+        .filter(|&i| Some(i) != root)
+        .map(component)
+        .collect();
+    let dependencies: Vec<Value> = sbom
+        .components
+        .iter()
+        .map(|c| {
+            json!({
+                "ref": c.key,
+                "dependsOn": c
+                    .depends_on
+                    .iter()
+                    .map(|&dep| &sbom.components[dep].key)
+                    .collect::<Vec<_>>(),
+            })
+        })
+        .collect();
+
+    let mut metadata = json!({
+        "timestamp": sbom.created,
+        "tools": {
+            "components": [{
+                "type": "application",
+                "name": env!("CARGO_PKG_NAME"),
+                "version": env!("CARGO_PKG_VERSION"),
+            }],
+        },
+    });
+    if let Some(root) = root {
+        metadata["component"] = component(root);
+    }
+
+    let doc = json!({
+        "bomFormat": "CycloneDX",
+        "specVersion": "1.5",
+        "serialNumber": format!("urn:uuid:{}", sbom.uuid),
+        "version": 1,
+        "metadata": metadata,
+        "components": components,
+        "dependencies": dependencies,
+    });
+    serde_json::to_string_pretty(&doc).expect("Unable to serialise SBOM")
+}
+
+/// A CycloneDX 1.5 document in the XML format.
+pub fn xml(sbom: &Sbom) -> String {
+    let mut out = String::new();
+    write_xml(sbom, &mut out).expect("Unable to write SBOM");
+    out
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn write_component(sbom: &Sbom, i: usize, indent: &str, w: &mut String) -> fmt::Result {
+    let c = &sbom.components[i];
+    writeln!(
+        w,
+        "{}<component type=\"{}\" bom-ref=\"{}\">",
+        indent,
+        component_type(sbom, i),
+        escape(&c.key)
+    )?;
+    writeln!(w, "{}  <name>{}</name>", indent, escape(&c.package.name))?;
+    writeln!(
+        w,
+        "{}  <version>{}</version>",
+        indent,
+        escape(&c.package.version)
+    )?;
+    if let Some(sum) = c.sha256 {
+        writeln!(w, "{}  <hashes>", indent)?;
+        writeln!(
+            w,
+            "{}    <hash alg=\"SHA-256\">{}</hash>",
+            indent,
+            escape(sum)
+        )?;
+        writeln!(w, "{}  </hashes>", indent)?;
+    }
+    if let Some(expr) = &c.concluded {
+        writeln!(w, "{}  <licenses>", indent)?;
+        match License::new(expr) {
+            License::Id(id) => writeln!(
+                w,
+                "{}    <license><id>{}</id></license>",
+                indent,
+                escape(id)
+            )?,
+            License::Name(name) => writeln!(
+                w,
+                "{}    <license><name>{}</name></license>",
+                indent,
+                escape(&name)
+            )?,
+            License::Expression(expr) => writeln!(
+                w,
+                "{}    <expression>{}</expression>",
+                indent,
+                escape(&expr)
+            )?,
+        }
+        writeln!(w, "{}  </licenses>", indent)?;
+    }
+    writeln!(w, "{}  <purl>{}</purl>", indent, escape(&c.purl))?;
+    if let Some((kind, url)) = reference(c) {
+        writeln!(w, "{}  <externalReferences>", indent)?;
+        writeln!(
+            w,
+            "{}    <reference type=\"{}\"><url>{}</url></reference>",
+            indent,
+            kind,
+            escape(url)
+        )?;
+        writeln!(w, "{}  </externalReferences>", indent)?;
+    }
+    writeln!(w, "{}</component>", indent)
+}
+
+fn write_xml(sbom: &Sbom, w: &mut String) -> fmt::Result {
+    writeln!(w, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>")?;
+    writeln!(
+        w,
+        "<bom xmlns=\"http://cyclonedx.org/schema/bom/1.5\" serialNumber=\"urn:uuid:{}\" version=\"1\">",
+        sbom.uuid
+    )?;
+
+    writeln!(w, "  <metadata>")?;
+    writeln!(w, "    <timestamp>{}</timestamp>", sbom.created)?;
+    writeln!(w, "    <tools>")?;
+    writeln!(w, "      <components>")?;
+    writeln!(w, "        <component type=\"application\">")?;
+    writeln!(w, "          <name>{}</name>", env!("CARGO_PKG_NAME"))?;
+    writeln!(
+        w,
+        "          <version>{}</version>",
+        env!("CARGO_PKG_VERSION")
+    )?;
+    writeln!(w, "        </component>")?;
+    writeln!(w, "      </components>")?;
+    writeln!(w, "    </tools>")?;
+    let root = sbom.roots.first().copied();
+    if let Some(root) = root {
+        write_component(sbom, root, "    ", w)?;
+    }
+    writeln!(w, "  </metadata>")?;
+
+    writeln!(w, "  <components>")?;
+    for i in (0..sbom.components.len()).filter(|&i| Some(i) != root) {
+        write_component(sbom, i, "    ", w)?;
+    }
+    writeln!(w, "  </components>")?;
+
+    writeln!(w, "  <dependencies>")?;
+    for c in &sbom.components {
+        if c.depends_on.is_empty() {
+            writeln!(w, "    <dependency ref=\"{}\"/>", escape(&c.key))?;
+            continue;
+        }
+        writeln!(w, "    <dependency ref=\"{}\">", escape(&c.key))?;
+        for &dep in &c.depends_on {
+            writeln!(
+                w,
+                "      <dependency ref=\"{}\"/>",
+                escape(&sbom.components[dep].key)
+            )?;
+        }
+        writeln!(w, "    </dependency>")?;
+    }
+    writeln!(w, "  </dependencies>")?;
+    writeln!(w, "</bom>")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sbom;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    fn created() -> std::time::SystemTime {
+        UNIX_EPOCH + Duration::from_secs(1_700_000_000)
+    }
+
+    fn by_name<'a>(components: &'a Value, name: &str) -> &'a Value {
+        components
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|c| c["name"] == name)
+            .unwrap()
+    }
+
+    #[test]
+    fn json_metadata_component() {
+        let (lock, report) = sbom::example();
+        let sbom = Sbom::new(&lock, None, &report, created());
+        let doc: Value = serde_json::from_str(&json(&sbom)).unwrap();
+
+        assert_eq!(doc["bomFormat"], "CycloneDX");
+        assert_eq!(doc["specVersion"], "1.5");
+        assert_eq!(doc["serialNumber"], format!("urn:uuid:{}", sbom.uuid));
+        assert_eq!(doc["metadata"]["timestamp"], "2023-11-14T22:13:20Z");
+        let app = &doc["metadata"]["component"];
+        assert_eq!(app["type"], "application");
+        assert_eq!(app["bom-ref"], "app-0.1.0");
+        assert_eq!(app["purl"], "pkg:cargo/app@0.1.0");
+        assert!(app.get("licenses").is_none());
+
+        // The subject of the document isn't repeated as a component.
+        let components = doc["components"].as_array().unwrap();
+        assert_eq!(components.len(), 3);
+        assert!(components.iter().all(|c| c["type"] == "library"));
+        let itoa = by_name(&doc["components"], "itoa");
+        assert_eq!(itoa["hashes"][0]["alg"], "SHA-256");
+        assert_eq!(
+            itoa["externalReferences"][0],
+            json!({
+                "type": "distribution",
+                "url": "https://crates.io/api/v1/crates/itoa/1.0.11/download",
+            })
+        );
+    }
+
+    #[test]
+    fn json_licenses() {
+        let (lock, report) = sbom::example();
+        let sbom = Sbom::new(&lock, None, &report, created());
+        let doc: Value = serde_json::from_str(&json(&sbom)).unwrap();
+        let components = &doc["components"];
+
+        assert_eq!(
+            by_name(components, "ryu")["licenses"],
+            json!([{ "license": { "id": "Apache-2.0" } }])
+        );
+        assert_eq!(
+            by_name(components, "custom")["licenses"],
+            json!([{ "license": { "name": "Custom-1.0" } }])
+        );
+        assert_eq!(
+            by_name(components, "itoa")["licenses"],
+            json!([{ "expression": "Apache-2.0 OR MIT" }])
+        );
+    }
+
+    #[test]
+    fn license_forms() {
+        let form = |expr: &str| match License::new(&LicenseExpr::parse(expr).unwrap()) {
+            License::Id(id) => format!("id {}", id),
+            License::Name(name) => format!("name {}", name),
+            License::Expression(expr) => format!("expression {}", expr),
+        };
+        assert_eq!(form("mit"), "id MIT");
+        assert_eq!(form("Custom-1.0"), "name Custom-1.0");
+        assert_eq!(form("LicenseRef-Mine"), "expression LicenseRef-Mine");
+        assert_eq!(form("GPL-2.0+"), "expression GPL-2.0+");
+        assert_eq!(
+            form("Apache-2.0 WITH LLVM-exception"),
+            "expression Apache-2.0 WITH LLVM-exception"
+        );
+        assert_eq!(
+            form("MIT AND Custom-1.0"),
+            "expression LicenseRef-Custom-1.0 AND MIT"
+        );
+    }
+
+    #[test]
+    fn json_dependencies() {
+        let (lock, report) = sbom::example();
+        let sbom = Sbom::new(&lock, None, &report, created());
+        let doc: Value = serde_json::from_str(&json(&sbom)).unwrap();
+        assert_eq!(
+            doc["dependencies"],
+            json!([
+                { "ref": "app-0.1.0", "dependsOn": ["custom-2.0.0", "itoa-1.0.11", "ryu-1.0.18"] },
+                { "ref": "custom-2.0.0", "dependsOn": ["ryu-1.0.18"] },
+                { "ref": "itoa-1.0.11", "dependsOn": [] },
+                { "ref": "ryu-1.0.18", "dependsOn": [] },
+            ])
+        );
+    }
+
+    #[test]
+    fn xml_document() {
+        let (lock, report) = sbom::example();
+        let sbom = Sbom::new(&lock, None, &report, created());
+        let doc = xml(&sbom);
+
+        let metadata = &doc[doc.find("<metadata>").unwrap()..doc.find("</metadata>").unwrap()];
+        assert!(metadata.contains(
+            "    <component type=\"application\" bom-ref=\"app-0.1.0\">\n      <name>app</name>\n"
+        ));
+        assert!(doc.contains("      <license><id>Apache-2.0</id></license>\n"));
+        assert!(doc.contains("      <license><name>Custom-1.0</name></license>\n"));
+        assert!(doc.contains("      <expression>Apache-2.0 OR MIT</expression>\n"));
+
+        let dependencies =
+            &doc[doc.find("<dependencies>").unwrap()..doc.find("</dependencies>").unwrap()];
+        assert_eq!(
+            dependencies,
+            "<dependencies>\n\
+             \x20   <dependency ref=\"app-0.1.0\">\n\
+             \x20     <dependency ref=\"custom-2.0.0\"/>\n\
+             \x20     <dependency ref=\"itoa-1.0.11\"/>\n\
+             \x20     <dependency ref=\"ryu-1.0.18\"/>\n\
+             \x20   </dependency>\n\
+             \x20   <dependency ref=\"custom-2.0.0\">\n\
+             \x20     <dependency ref=\"ryu-1.0.18\"/>\n\
+             \x20   </dependency>\n\
+             \x20   <dependency ref=\"itoa-1.0.11\"/>\n\
+             \x20   <dependency ref=\"ryu-1.0.18\"/>\n  "
+        );
+    }
+
+    #[test]
+    fn xml_escaping() {
+        assert_eq!(escape(r#"a<b>&"c""#), "a&lt;b&gt;&amp;&quot;c&quot;");
+        assert_eq!(escape("&amp;"), "&amp;amp;");
+
+        let (lock, report) = sbom::example();
+        let mut sbom = Sbom::new(&lock, None, &report, created());
+        sbom.components[2].key = "itoa<&>".to_string();
+        let doc = xml(&sbom);
+        assert!(doc.contains("<component type=\"library\" bom-ref=\"itoa&lt;&amp;&gt;\">"));
+        assert!(doc.contains("<dependency ref=\"itoa&lt;&amp;&gt;\"/>"));
+        assert!(!doc.contains("<&>"));
+    }
+}
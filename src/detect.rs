@@ -0,0 +1,202 @@
+use std::collections::BTreeMap;
+use std::sync::OnceLock;
+
+/// License texts from the SPDX license list, for the licenses commonly found
+/// in crates. https://github.com/spdx/license-list-data
+const TEMPLATES: &[(&str, &str)] = &[
+    ("0BSD", include_str!("licenses/0BSD.txt")),
+    ("Apache-2.0", include_str!("licenses/Apache-2.0.txt")),
+    ("BSD-2-Clause", include_str!("licenses/BSD-2-Clause.txt")),
+    ("BSD-3-Clause", include_str!("licenses/BSD-3-Clause.txt")),
+    ("BSL-1.0", include_str!("licenses/BSL-1.0.txt")),
+    ("CC0-1.0", include_str!("licenses/CC0-1.0.txt")),
+    ("GPL-2.0-only", include_str!("licenses/GPL-2.0-only.txt")),
+    ("GPL-3.0-only", include_str!("licenses/GPL-3.0-only.txt")),
+    ("ISC", include_str!("licenses/ISC.txt")),
+    ("LGPL-2.1-only", include_str!("licenses/LGPL-2.1-only.txt")),
+    ("LGPL-3.0-only", include_str!("licenses/LGPL-3.0-only.txt")),
+    ("MIT", include_str!("licenses/MIT.txt")),
+    ("MIT-0", include_str!("licenses/MIT-0.txt")),
+    ("MPL-2.0", include_str!("licenses/MPL-2.0.txt")),
+    ("OpenSSL", include_str!("licenses/OpenSSL.txt")),
+    ("Unicode-3.0", include_str!("licenses/Unicode-3.0.txt")),
+    (
+        "Unicode-DFS-2016",
+        include_str!("licenses/Unicode-DFS-2016.txt"),
+    ),
+    ("Unlicense", include_str!("licenses/Unlicense.txt")),
+    ("WTFPL", include_str!("licenses/WTFPL.txt")),
+    ("Zlib", include_str!("licenses/Zlib.txt")),
+];
+
+/// Scores below this aren't considered a match at all.
+const MINIMUM_SCORE: f32 = 0.7;
+/// Scores below this are reported, but need a human to confirm them.
+pub const CONFIDENT_SCORE: f32 = 0.9;
+
+/// The best template match for a license text.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Match {
+    pub id: &'static str,
+    /// How similar the texts are, from 0.0 to 1.0.
+    pub score: f32,
+}
+
+impl Match {
+    pub fn is_confident(&self) -> bool {
+        self.score >= CONFIDENT_SCORE
+    }
+}
+
+type Bigrams = BTreeMap<(String, String), usize>;
+
+/// Count word pairs, ignoring case, punctuation, layout and copyright lines,
+/// which differ between every copy of a license.
+fn bigrams(text: &str) -> Bigrams {
+    let words: Vec<String> = text
+        .lines()
+        .filter(|l| !l.trim_start().to_lowercase().starts_with("copyright"))
+        .flat_map(|l| {
+            l.split(|c: char| !c.is_alphanumeric())
+                .filter(|w| !w.is_empty())
+                .map(str::to_lowercase)
+                .collect::<Vec<_>>()
+        })
+        .collect();
+
+    let mut counts = BTreeMap::new();
+    for pair in words.windows(2) {
+        *counts
+            .entry((pair[0].clone(), pair[1].clone()))
+            .or_insert(0) += 1;
+    }
+    counts
+}
+
+/// The Sørensen–Dice coefficient of two bigram multisets.
+fn similarity(a: &Bigrams, b: &Bigrams) -> f32 {
+    let total: usize = a.values().sum::<usize>() + b.values().sum::<usize>();
+    if total == 0 {
+        return 0.0;
+    }
+    let shared: usize = a
+        .iter()
+        .filter_map(|(k, n)| b.get(k).map(|m| n.min(m)))
+        .sum();
+    (2 * shared) as f32 / total as f32
+}
+
+fn templates() -> &'static [(&'static str, Bigrams)] {
+    static CACHE: OnceLock<Vec<(&'static str, Bigrams)>> = OnceLock::new();
+    CACHE.get_or_init(|| {
+        TEMPLATES
+            .iter()
+            .map(|(id, text)| (*id, bigrams(text)))
+            .collect()
+    })
+}
+
+/// Identify the license in a license text.
+pub fn classify(text: &str) -> Option<Match> {
+    let text = bigrams(text);
+    templates()
+        .iter()
+        .map(|(id, template)| Match {
+            id,
+            score: similarity(&text, template),
+        })
+        .filter(|m| m.score >= MINIMUM_SCORE)
+        .max_by(|a, b| a.score.total_cmp(&b.score))
+}
+
+/// Is this a file that conventionally holds a license text?
+pub fn is_license_file(name: &str) -> bool {
+    let name = name.to_uppercase();
+    name.starts_with("LICENSE") || name.starts_with("LICENCE") || name.starts_with("COPYING")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MIT: &str = include_str!("licenses/MIT.txt");
+
+    /// A clause that isn't part of any license on the list.
+    const EXTRA: &str = "\n\nThe Software shall be used for Good, not Evil. Any person who uses \
+        the Software to harm others or to build weapons of any kind loses every right granted by \
+        this license, immediately and without notice.\n";
+
+    #[test]
+    fn templates_match_themselves() {
+        for (id, text) in TEMPLATES {
+            assert_eq!(classify(text), Some(Match { id, score: 1.0 }), "{}", id);
+        }
+    }
+
+    #[test]
+    fn copyright_lines_and_layout_are_ignored() {
+        // Rewrapped, with the copyright lines of a real crate.
+        let text: Vec<String> = MIT
+            .split("\n\n")
+            .map(|p| {
+                if p.starts_with("Copyright") {
+                    "Copyright (c) 2014-2024 The Rust Project Developers\n\
+                     Copyright (c) 2024 Someone Else <someone@example.com>"
+                        .to_string()
+                } else {
+                    p.replace('\n', " ").replace(". ", ".\n    ")
+                }
+            })
+            .collect();
+        let text = text.join("\n\n");
+        let m = classify(&text).unwrap();
+        assert_eq!(m.id, "MIT");
+        assert_eq!(m.score, 1.0);
+        assert!(m.is_confident());
+    }
+
+    #[test]
+    fn modified_licenses_have_low_confidence() {
+        let text = format!("{}{}{}", MIT, EXTRA, EXTRA.replace("Good", "Bad"));
+        let m = classify(&text).unwrap();
+        assert_eq!(m.id, "MIT");
+        assert!(m.score >= MINIMUM_SCORE && !m.is_confident(), "{}", m.score);
+    }
+
+    #[test]
+    fn partial_licenses_are_not_reported() {
+        // Only the permission and notice paragraphs, without the disclaimer.
+        let paragraphs: Vec<&str> = MIT.split("\n\n").collect();
+        let text = paragraphs[2..4].join("\n\n");
+        assert!(similarity(&bigrams(&text), &bigrams(MIT)) < MINIMUM_SCORE);
+        assert_eq!(classify(&text), None);
+    }
+
+    #[test]
+    fn other_text() {
+        assert_eq!(classify(""), None);
+        assert_eq!(classify(EXTRA), None);
+        let readme = "Generate RPM Provides and License tags from a Cargo.lock and the \
+            matching vendored crates. The vendor directory may also be a vendor tarball, which \
+            is read directly without unpacking it.";
+        assert_eq!(classify(readme), None);
+    }
+
+    #[test]
+    fn license_file_names() {
+        for name in [
+            "LICENSE",
+            "LICENSE-MIT",
+            "license.txt",
+            "Licence.md",
+            "COPYING",
+        ]
+        .iter()
+        {
+            assert!(is_license_file(name), "{}", name);
+        }
+        for name in ["README.md", "UNLICENSE", "src", "Cargo.toml"].iter() {
+            assert!(!is_license_file(name), "{}", name);
+        }
+    }
+}
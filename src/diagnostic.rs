@@ -0,0 +1,85 @@
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Debug,
+    Warning,
+    /// Something that must fail the run, such as a license policy violation.
+    Error,
+}
+
+/// A locked package that a diagnostic is about.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PackageRef {
+    pub name: String,
+    pub version: String,
+}
+
+/// A message about the lockfile, vendor tree or a crate in it, that would
+/// otherwise go to stderr.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub level: Level,
+    pub package: Option<PackageRef>,
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.level {
+            Level::Debug => write!(f, "DEBUG -> ")?,
+            Level::Warning => write!(f, "WARNING ")?,
+            Level::Error => write!(f, "ERROR ")?,
+        }
+        if let Some(pkg) = &self.package {
+            write!(f, "{} {} - ", pkg.name, pkg.version)?;
+        }
+        write!(f, "{}", self.message)
+    }
+}
+
+/// Collects diagnostics while a report is generated. Debug messages are
+/// dropped unless debugging was asked for.
+#[derive(Debug, Default)]
+pub struct Diagnostics {
+    debug: bool,
+    entries: Vec<Diagnostic>,
+}
+
+impl Diagnostics {
+    pub fn new(debug: bool) -> Self {
+        Diagnostics {
+            debug,
+            entries: Vec::new(),
+        }
+    }
+
+    pub fn add(&mut self, level: Level, message: impl Into<String>) {
+        if level == Level::Debug && !self.debug {
+            return;
+        }
+        self.entries.push(Diagnostic {
+            level,
+            package: None,
+            message: message.into(),
+        });
+    }
+
+    pub fn add_for(&mut self, level: Level, name: &str, version: &str, message: impl Into<String>) {
+        if level == Level::Debug && !self.debug {
+            return;
+        }
+        self.entries.push(Diagnostic {
+            level,
+            package: Some(PackageRef {
+                name: name.to_string(),
+                version: version.to_string(),
+            }),
+            message: message.into(),
+        });
+    }
+
+    pub fn into_vec(self) -> Vec<Diagnostic> {
+        self.entries
+    }
+}
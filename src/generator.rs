@@ -0,0 +1,204 @@
+//! The rpm fileattrs dependency generator, which finds the bundled crates of
+//! every installed Rust binary.
+
+use crate::auditable;
+use crate::diagnostic::{Diagnostic, Diagnostics, Level};
+use crate::lockfile::{LockError, LockFile};
+use crate::report::{Options, Report};
+use crate::vendor::Vendor;
+use crate::workspace::Workspace;
+use std::collections::BTreeSet;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug)]
+pub enum GeneratorError {
+    Io(PathBuf, io::Error),
+    Lock(PathBuf, LockError),
+}
+
+impl fmt::Display for GeneratorError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GeneratorError::Io(path, e) => write!(f, "unable to read {:?} - {}", path, e),
+            GeneratorError::Lock(path, e) => write!(f, "unable to parse {:?} - {}", path, e),
+        }
+    }
+}
+
+impl std::error::Error for GeneratorError {}
+
+/// Finds the provides of each file rpm passes to the generator. Each provide
+/// is only returned once.
+pub struct Generator<'a> {
+    lockdir: Option<&'a Path>,
+    vendordir: &'a Path,
+    options: &'a Options,
+    /// The provides from the Cargo.lock, which is only read if a Rust
+    /// binary without auditable data needs it, and then only once.
+    fallback: Option<Vec<String>>,
+    seen: BTreeSet<String>,
+}
+
+impl<'a> Generator<'a> {
+    /// Binaries built without cargo auditable fall back to the Cargo.lock in
+    /// `lockdir`, and the vendor tree if there is one.
+    pub fn new(lockdir: Option<&'a Path>, vendordir: &'a Path, options: &'a Options) -> Self {
+        Generator {
+            lockdir,
+            vendordir,
+            options,
+            fallback: None,
+            seen: BTreeSet::new(),
+        }
+    }
+
+    /// The provides of a file that weren't returned for an earlier one, and
+    /// the diagnostics found on the way. Files that aren't Rust binaries
+    /// provide nothing.
+    pub fn provides(
+        &mut self,
+        file: &Path,
+    ) -> Result<(Vec<String>, Vec<Diagnostic>), GeneratorError> {
+        let mut diags = Diagnostics::new(self.options.debug);
+        let buffer = match std::fs::read(file) {
+            Ok(buffer) => buffer,
+            Err(e) => {
+                diags.add(Level::Debug, format!("unable to read {:?} - {}", file, e));
+                return Ok((Vec::new(), diags.into_vec()));
+            }
+        };
+
+        let mut diagnostics = Vec::new();
+        let provides = match auditable::read(&buffer) {
+            Ok(lock) => {
+                let report = Report::generate(&lock, None, None, self.options);
+                diagnostics = report.diagnostics.clone();
+                report.spec_provides()
+            }
+            Err(e) => {
+                diags.add(Level::Debug, format!("{:?} - {}", file, e));
+                match self.lockdir {
+                    Some(dir) if dir.join("Cargo.lock").exists() && auditable::is_rust(&buffer) => {
+                        if self.fallback.is_none() {
+                            let report = self.fallback_report(dir, &mut diags)?;
+                            diagnostics = report.diagnostics.clone();
+                            self.fallback = Some(report.spec_provides());
+                        }
+                        self.fallback.clone().unwrap_or_default()
+                    }
+                    _ => Vec::new(),
+                }
+            }
+        };
+
+        let provides = provides
+            .into_iter()
+            .filter(|p| self.seen.insert(p.clone()))
+            .collect();
+        let mut diags = diags.into_vec();
+        diags.extend(diagnostics);
+        Ok((provides, diags))
+    }
+
+    fn fallback_report(
+        &self,
+        dir: &Path,
+        diags: &mut Diagnostics,
+    ) -> Result<Report, GeneratorError> {
+        let lockfile = dir.join("Cargo.lock");
+        let buffer =
+            std::fs::read(&lockfile).map_err(|e| GeneratorError::Io(lockfile.clone(), e))?;
+        let lock = LockFile::parse(&buffer).map_err(|e| GeneratorError::Lock(lockfile, e))?;
+        let workspace = Workspace::load(dir);
+        // The vendor dir is optional, don't complain in every build log
+        // without one.
+        let vendor = if self.vendordir.exists() {
+            match Vendor::open(self.vendordir.to_path_buf()) {
+                Ok(vendor) => Some(vendor),
+                Err(e) => {
+                    diags.add(
+                        Level::Warning,
+                        format!("could not read vendor - {:?} - {}", self.vendordir, e),
+                    );
+                    None
+                }
+            }
+        } else {
+            None
+        };
+        Ok(Report::generate(
+            &lock,
+            workspace.as_ref(),
+            vendor.as_ref(),
+            self.options,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auditable::tests::{elf, zlib, JSON};
+
+    #[test]
+    fn provides_are_only_returned_once() {
+        let dir = std::env::temp_dir().join(format!("generator-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let binary = elf(&[(auditable::SECTION, &zlib(JSON.as_bytes()))]);
+        std::fs::write(dir.join("a"), &binary).unwrap();
+        std::fs::write(dir.join("b"), &binary).unwrap();
+        std::fs::write(dir.join("script"), "#!/bin/sh\n").unwrap();
+
+        let options = Options::default();
+        let vendordir = dir.join("vendor");
+        let mut generator = Generator::new(None, &vendordir, &options);
+        let provides =
+            |generator: &mut Generator, name| generator.provides(&dir.join(name)).unwrap().0;
+        assert_eq!(
+            provides(&mut generator, "a"),
+            ["bundled(crate(itoa)) = 1.0.11"]
+        );
+        assert!(provides(&mut generator, "b").is_empty());
+        assert!(provides(&mut generator, "script").is_empty());
+        assert!(provides(&mut generator, "missing").is_empty());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn rust_binaries_without_auditable_data_use_the_lockfile() {
+        let dir = std::env::temp_dir().join(format!("generator-lock-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("Cargo.lock"),
+            r#"
+version = 3
+
+[[package]]
+name = "itoa"
+version = "1.0.11"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+"#,
+        )
+        .unwrap();
+        let rust = elf(&[(".comment", b"rustc"), (".rodata", b"/rustc/abc/library")]);
+        std::fs::write(dir.join("rust"), &rust).unwrap();
+        let c = elf(&[(".comment", b"GCC")]);
+        std::fs::write(dir.join("c"), &c).unwrap();
+
+        let options = Options::default();
+        let vendordir = dir.join("vendor");
+        let mut generator = Generator::new(Some(&dir), &vendordir, &options);
+        assert!(generator.provides(&dir.join("c")).unwrap().0.is_empty());
+        assert_eq!(
+            generator.provides(&dir.join("rust")).unwrap().0,
+            ["bundled(crate(itoa)) = 1.0.11"]
+        );
+
+        // Without a lockfile there is nothing to fall back to.
+        let mut generator = Generator::new(None, &vendordir, &options);
+        assert!(generator.provides(&dir.join("rust")).unwrap().0.is_empty());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,665 @@
+//! Walk the dependency graph of a lockfile from the workspace members, to
+//! find the packages that are actually built.
+//!
+//! Cargo.lock records every dependency for every platform, so the edges are
+//! narrowed using the manifests the dependencies were declared in.
+
+use crate::diagnostic::{Diagnostics, Level};
+use crate::lockfile::{LockFile, LockedPackage, SourceKind};
+use crate::manifest::{DepKind, DepSpec, Manifest};
+use crate::target::Target;
+use crate::vendor::Vendor;
+use crate::workspace::Workspace;
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::path::Path;
+
+/// Indexes of the packages the graph starts from: the workspace members, or
+/// without a workspace the local packages nothing depends on.
+pub fn roots(lock: &LockFile, workspace: Option<&Workspace>) -> Vec<usize> {
+    let local = |i: &usize| lock.packages[*i].kind() == SourceKind::Local;
+    let roots: Vec<usize> = match workspace {
+        Some(ws) => (0..lock.packages.len())
+            .filter(local)
+            .filter(|&i| ws.is_member(&lock.packages[i].name))
+            .collect(),
+        None => Vec::new(),
+    };
+    if !roots.is_empty() {
+        return roots;
+    }
+    (0..lock.packages.len())
+        .filter(local)
+        .filter(|&i| {
+            !lock
+                .packages
+                .iter()
+                .any(|p| p.dependencies.iter().any(|d| lock.packages[i].matches(d)))
+        })
+        .collect()
+}
+
+/// The features to enable on the workspace members, as given to cargo.
+#[derive(Debug, Clone, Default)]
+pub struct Features {
+    /// Features of every member, or `member/feature` for just one.
+    pub features: Vec<String>,
+    pub all_features: bool,
+    pub no_default_features: bool,
+}
+
+/// A package's manifest, as far as the graph needs it.
+struct Declared {
+    deps: Vec<DepSpec>,
+    features: BTreeMap<String, Vec<String>>,
+}
+
+/// The dependencies declared by a package, from its vendored Cargo.toml or
+/// the workspace member's own. None if the manifest can't be found.
+fn declared(
+    pkg: &LockedPackage,
+    workspace: Option<&Workspace>,
+    vendor: Option<&Vendor>,
+) -> Option<Declared> {
+    let manifest = if pkg.kind() == SourceKind::Local {
+        let member = workspace?.members.iter().find(|m| m.name == pkg.name)?;
+        Manifest::load(&Vendor::Dir(member.path.clone()), Path::new("")).ok()?
+    } else {
+        let vendor = vendor?;
+        let dir = vendor.locate(&pkg.name, &pkg.version)?;
+        Manifest::load(vendor, &dir).ok()?
+    };
+    Some(Declared {
+        deps: manifest.dependencies(),
+        features: manifest.features(),
+    })
+}
+
+/// What a set of features turns on in a package.
+#[derive(Debug, Default)]
+struct Enabled {
+    /// The features, with every feature they enable in turn.
+    features: BTreeSet<String>,
+    /// The keys of the optional dependencies that are used.
+    deps: BTreeSet<String>,
+    /// Features of dependencies, by key.
+    dep_features: Vec<(String, String)>,
+}
+
+fn enable(declared: &Declared, features: &BTreeSet<String>) -> Enabled {
+    // An optional dependency only has an implicit feature of the same name
+    // if no feature refers to it as `dep:name`.
+    let explicit: BTreeSet<&str> = declared
+        .features
+        .values()
+        .flatten()
+        .filter_map(|f| f.strip_prefix("dep:"))
+        .collect();
+    let optional = |key: &str| declared.deps.iter().any(|d| d.optional && d.key == key);
+
+    let mut enabled = Enabled::default();
+    let mut seen = BTreeSet::new();
+    let mut stack: Vec<&str> = features.iter().map(String::as_str).collect();
+    while let Some(feature) = stack.pop() {
+        if !seen.insert(feature) {
+            continue;
+        }
+        match declared.features.get(feature) {
+            Some(entries) => {
+                for entry in entries {
+                    if let Some(dep) = entry.strip_prefix("dep:") {
+                        enabled.deps.insert(dep.to_string());
+                    } else if let Some((dep, dep_feature)) = entry.split_once('/') {
+                        // `dep?/feature` doesn't enable the dependency itself.
+                        let dep = match dep.strip_suffix('?') {
+                            Some(dep) => dep,
+                            None => {
+                                enabled.deps.insert(dep.to_string());
+                                dep
+                            }
+                        };
+                        enabled
+                            .dep_features
+                            .push((dep.to_string(), dep_feature.to_string()));
+                    } else {
+                        stack.push(entry);
+                    }
+                }
+            }
+            None if optional(feature) && !explicit.contains(feature) => {
+                enabled.deps.insert(feature.to_string());
+            }
+            None => {}
+        }
+    }
+    enabled.features = seen.into_iter().map(str::to_string).collect();
+    enabled
+}
+
+/// The features asked for on a root package.
+fn root_features(
+    pkg: &LockedPackage,
+    declared: Option<&Declared>,
+    features: &Features,
+    diags: &mut Diagnostics,
+) -> BTreeSet<String> {
+    let mut enabled = BTreeSet::new();
+    if !features.no_default_features {
+        enabled.insert("default".to_string());
+    }
+    if let (true, Some(declared)) = (features.all_features, declared) {
+        enabled.extend(declared.features.keys().cloned());
+        enabled.extend(
+            declared
+                .deps
+                .iter()
+                .filter(|d| d.optional)
+                .map(|d| d.key.clone()),
+        );
+    }
+    for feature in features.features.iter().flat_map(|f| f.split_whitespace()) {
+        let feature = match feature.split_once('/') {
+            Some((member, feature)) if member == pkg.name => feature,
+            Some(_) => continue,
+            None => feature,
+        };
+        let known = declared.is_none_or(|d| {
+            d.features.contains_key(feature)
+                || d.deps.iter().any(|s| s.optional && s.key == feature)
+        });
+        if !known {
+            diags.add_for(
+                Level::Warning,
+                &pkg.name,
+                &pkg.version,
+                format!("feature {} is not defined", feature),
+            );
+        }
+        enabled.insert(feature.to_string());
+    }
+    enabled
+}
+
+/// What walking the dependency graph found out, indexed like the locked
+/// packages.
+#[derive(Debug)]
+pub struct Classes {
+    /// How each package is needed by the roots: through normal dependencies
+    /// only, through a build-dependency, or only as a dev-dependency. None if
+    /// it isn't reachable at all, which with a `target` means it is only
+    /// needed on other platforms, and with `features` that no enabled
+    /// feature uses it.
+    pub kinds: Vec<Option<DepKind>>,
+    /// The features enabled on each package, including those they imply.
+    /// None if features weren't resolved, or the manifest wasn't found.
+    pub features: Vec<Option<BTreeSet<String>>>,
+}
+
+/// Walk the dependency graph from the roots.
+///
+/// Anything that can't be resolved is assumed to be a normal dependency, so
+/// that a missing manifest never drops a crate that is really bundled.
+pub fn classify(
+    lock: &LockFile,
+    workspace: Option<&Workspace>,
+    vendor: Option<&Vendor>,
+    target: Option<&Target>,
+    features: Option<&Features>,
+    diags: &mut Diagnostics,
+) -> Classes {
+    let roots = roots(lock, workspace);
+    if roots.is_empty() {
+        diags.add(
+            Level::Warning,
+            "no root package found, every locked package is assumed to be needed",
+        );
+        return Classes {
+            kinds: vec![Some(DepKind::Normal); lock.packages.len()],
+            features: vec![None; lock.packages.len()],
+        };
+    }
+
+    let mut kinds = vec![None; lock.packages.len()];
+    let mut enabled = vec![BTreeSet::new(); lock.packages.len()];
+    let mut manifests: Vec<Option<Option<Declared>>> =
+        (0..lock.packages.len()).map(|_| None).collect();
+    for &i in &roots {
+        let pkg = &lock.packages[i];
+        kinds[i] = Some(DepKind::Normal);
+        let declared = manifests[i].get_or_insert_with(|| declared(pkg, workspace, vendor));
+        if let Some(features) = features {
+            enabled[i] = root_features(pkg, declared.as_ref(), features, diags);
+        }
+    }
+
+    let mut is_root = vec![false; lock.packages.len()];
+    for &i in &roots {
+        is_root[i] = true;
+    }
+    let mut queue: VecDeque<usize> = roots.into_iter().collect();
+    while let Some(i) = queue.pop_front() {
+        let pkg = &lock.packages[i];
+        let kind = match kinds[i] {
+            Some(kind) => kind,
+            None => continue,
+        };
+        let declared = manifests[i].get_or_insert_with(|| {
+            let declared = declared(pkg, workspace, vendor);
+            if declared.is_none() {
+                diags.add_for(
+                    Level::Debug,
+                    &pkg.name,
+                    &pkg.version,
+                    "no manifest found, following every locked dependency",
+                );
+            }
+            declared
+        });
+        let turned_on = match (features, declared.as_ref()) {
+            (Some(_), Some(declared)) => Some(enable(declared, &enabled[i])),
+            _ => None,
+        };
+
+        for dep in &pkg.dependencies {
+            let j = match lock.packages.iter().position(|p| p.matches(dep)) {
+                Some(j) => j,
+                None => continue,
+            };
+            let named: Vec<&DepSpec> = declared
+                .iter()
+                .flat_map(|d| &d.deps)
+                .filter(|s| s.name == dep.name)
+                .collect();
+            let mut requested = BTreeSet::new();
+            let edge = if named.is_empty() {
+                Some(DepKind::Normal)
+            } else {
+                let used: Vec<&DepSpec> = named
+                    .into_iter()
+                    // Only the workspace's own dev-dependencies are ever built.
+                    .filter(|s| s.kind != DepKind::Dev || is_root[i])
+                    .filter(|s| match (&s.target, target) {
+                        (Some(platform), Some(target)) => {
+                            target.matches_platform(platform).unwrap_or_else(|e| {
+                                diags.add_for(
+                                    Level::Warning,
+                                    &pkg.name,
+                                    &pkg.version,
+                                    format!("[target.{}] - {}", platform, e),
+                                );
+                                true
+                            })
+                        }
+                        _ => true,
+                    })
+                    .filter(|s| {
+                        !s.optional || turned_on.as_ref().is_none_or(|t| t.deps.contains(&s.key))
+                    })
+                    .collect();
+                for spec in &used {
+                    requested.extend(spec.features.iter().cloned());
+                    if spec.default_features {
+                        requested.insert("default".to_string());
+                    }
+                    for (key, feature) in turned_on.iter().flat_map(|t| &t.dep_features) {
+                        if key == &spec.key {
+                            requested.insert(feature.clone());
+                        }
+                    }
+                }
+                used.iter().map(|s| s.kind).min()
+            };
+
+            // A package is only as needed as the least needed link on the
+            // way to it, and takes the most needed of all the ways.
+            if let Some(edge) = edge {
+                let via = kind.max(edge);
+                let mut changed = kinds[j].is_none_or(|k| via < k);
+                if changed {
+                    kinds[j] = Some(via);
+                }
+                if features.is_some() && !requested.is_subset(&enabled[j]) {
+                    enabled[j].extend(requested);
+                    changed = true;
+                }
+                if changed {
+                    queue.push_back(j);
+                }
+            }
+        }
+    }
+
+    let mut resolved = vec![None; lock.packages.len()];
+    if features.is_some() {
+        for (i, pkg) in lock.packages.iter().enumerate() {
+            if kinds[i].is_none() {
+                continue;
+            }
+            if !enabled[i].is_empty() {
+                let list: Vec<&str> = enabled[i].iter().map(String::as_str).collect();
+                diags.add_for(
+                    Level::Debug,
+                    &pkg.name,
+                    &pkg.version,
+                    format!("features {}", list.join(", ")),
+                );
+            }
+            if let Some(Some(declared)) = &manifests[i] {
+                resolved[i] = Some(enable(declared, &enabled[i]).features);
+            }
+        }
+    }
+    Classes {
+        kinds,
+        features: resolved,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::workspace::Member;
+    use std::path::PathBuf;
+
+    fn declared(manifest: &str) -> Declared {
+        let vendor = Vendor::from_files(&[("pkg/Cargo.toml", manifest)]);
+        let manifest = Manifest::load(&vendor, Path::new("pkg")).unwrap();
+        Declared {
+            deps: manifest.dependencies(),
+            features: manifest.features(),
+        }
+    }
+
+    fn set(features: &[&str]) -> BTreeSet<String> {
+        features.iter().map(|f| f.to_string()).collect()
+    }
+
+    /// A workspace with the single member `app`. Members are read from their
+    /// directory, so this one is written to disk.
+    struct App {
+        dir: PathBuf,
+        workspace: Workspace,
+    }
+
+    impl App {
+        fn new(test: &str, manifest: &str) -> Self {
+            let dir = std::env::temp_dir().join(format!("graph-{}-{}", test, std::process::id()));
+            std::fs::create_dir_all(&dir).unwrap();
+            std::fs::write(dir.join("Cargo.toml"), manifest).unwrap();
+            let workspace = Workspace {
+                members: vec![Member {
+                    name: "app".to_string(),
+                    path: dir.clone(),
+                }],
+            };
+            App { dir, workspace }
+        }
+    }
+
+    impl Drop for App {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.dir);
+        }
+    }
+
+    /// A lockfile of `app` and registry packages at 1.0.0, each with its
+    /// locked dependencies.
+    fn lock(packages: &[(&str, &[&str])]) -> LockFile {
+        let mut text = String::from("version = 4\n");
+        for (name, deps) in packages {
+            text.push_str(&format!(
+                "\n[[package]]\nname = \"{}\"\nversion = \"{}\"\n",
+                name,
+                if *name == "app" { "0.1.0" } else { "1.0.0" }
+            ));
+            if *name != "app" {
+                text.push_str(
+                    "source = \"registry+https://github.com/rust-lang/crates.io-index\"\n",
+                );
+            }
+            let deps: Vec<String> = deps.iter().map(|d| format!("{:?}", d)).collect();
+            text.push_str(&format!("dependencies = [{}]\n", deps.join(", ")));
+        }
+        LockFile::parse(text.as_bytes()).unwrap()
+    }
+
+    fn kind(lock: &LockFile, classes: &Classes, name: &str) -> Option<DepKind> {
+        let i = lock.packages.iter().position(|p| p.name == name).unwrap();
+        classes.kinds[i]
+    }
+
+    const OPTIONAL: &str = r#"
+[package]
+name = "pkg"
+version = "1.0.0"
+
+[dependencies]
+foo = { version = "1", optional = true }
+bar = { version = "1", optional = true }
+
+[features]
+default = ["std"]
+std = []
+with-foo = ["dep:foo"]
+bar-std = ["bar?/std"]
+foo-std = ["foo/std"]
+"#;
+
+    #[test]
+    fn dep_syntax_hides_the_implicit_feature() {
+        let declared = declared(OPTIONAL);
+        assert!(enable(&declared, &set(&["foo"])).deps.is_empty());
+        assert_eq!(enable(&declared, &set(&["with-foo"])).deps, set(&["foo"]));
+        // bar has no dep:bar anywhere, so its implicit feature remains.
+        assert_eq!(enable(&declared, &set(&["bar"])).deps, set(&["bar"]));
+    }
+
+    #[test]
+    fn weak_dependency_features() {
+        let declared = declared(OPTIONAL);
+        let weak = enable(&declared, &set(&["bar-std"]));
+        assert!(weak.deps.is_empty());
+        assert_eq!(weak.dep_features, [("bar".to_string(), "std".to_string())]);
+
+        let strong = enable(&declared, &set(&["foo-std"]));
+        assert_eq!(strong.deps, set(&["foo"]));
+        assert_eq!(
+            strong.dep_features,
+            [("foo".to_string(), "std".to_string())]
+        );
+    }
+
+    #[test]
+    fn features_include_those_they_imply() {
+        let declared = declared(OPTIONAL);
+        assert_eq!(
+            enable(&declared, &set(&["default"])).features,
+            set(&["default", "std"])
+        );
+    }
+
+    #[test]
+    fn root_feature_selection() {
+        let lock = lock(&[("app", &[])]);
+        let app = &lock.packages[0];
+        let declared = declared(OPTIONAL);
+        let mut diags = Diagnostics::new(false);
+
+        let defaults = Features::default();
+        assert_eq!(
+            root_features(app, Some(&declared), &defaults, &mut diags),
+            set(&["default"])
+        );
+
+        let none = Features {
+            no_default_features: true,
+            ..Features::default()
+        };
+        assert!(root_features(app, Some(&declared), &none, &mut diags).is_empty());
+
+        let all = Features {
+            all_features: true,
+            no_default_features: true,
+            ..Features::default()
+        };
+        assert_eq!(
+            root_features(app, Some(&declared), &all, &mut diags),
+            set(&["bar", "bar-std", "default", "foo", "foo-std", "std", "with-foo"])
+        );
+
+        // member/feature only applies to that member.
+        let scoped = Features {
+            features: vec!["app/std other/std".to_string(), "with-foo".to_string()],
+            no_default_features: true,
+            ..Features::default()
+        };
+        assert_eq!(
+            root_features(app, Some(&declared), &scoped, &mut diags),
+            set(&["std", "with-foo"])
+        );
+        assert!(diags.into_vec().is_empty());
+    }
+
+    #[test]
+    fn unknown_features_are_warned_about() {
+        let lock = lock(&[("app", &[])]);
+        let features = Features {
+            features: vec!["nope".to_string()],
+            ..Features::default()
+        };
+        let mut diags = Diagnostics::new(false);
+        let enabled = root_features(
+            &lock.packages[0],
+            Some(&declared(OPTIONAL)),
+            &features,
+            &mut diags,
+        );
+        assert!(enabled.contains("nope"));
+        let diags = diags.into_vec();
+        assert_eq!(diags.len(), 1);
+        assert_eq!(diags[0].level, Level::Warning);
+        assert_eq!(diags[0].message, "feature nope is not defined");
+    }
+
+    #[test]
+    fn features_select_optional_dependencies() {
+        let app = App::new(
+            "features",
+            r#"
+[package]
+name = "app"
+version = "0.1.0"
+
+[dependencies]
+foo = { version = "1", optional = true }
+bar = { version = "1", default-features = false }
+
+[features]
+default = ["bar/std"]
+with-foo = ["dep:foo"]
+"#,
+        );
+        let lock = lock(&[("app", &["foo", "bar"]), ("foo", &[]), ("bar", &[])]);
+        let vendor = Vendor::from_files(&[
+            (
+                "foo/Cargo.toml",
+                "[package]\nname = \"foo\"\nversion = \"1.0.0\"\n",
+            ),
+            (
+                "bar/Cargo.toml",
+                "[package]\nname = \"bar\"\nversion = \"1.0.0\"\n\n\
+                 [features]\ndefault = [\"std\"]\nstd = [\"alloc\"]\nalloc = []\n",
+            ),
+        ]);
+
+        let mut diags = Diagnostics::new(false);
+        let classes = classify(
+            &lock,
+            Some(&app.workspace),
+            Some(&vendor),
+            None,
+            Some(&Features::default()),
+            &mut diags,
+        );
+        assert_eq!(kind(&lock, &classes, "foo"), None);
+        assert_eq!(kind(&lock, &classes, "bar"), Some(DepKind::Normal));
+        assert_eq!(classes.features[2], Some(set(&["alloc", "std"])));
+
+        let with_foo = Features {
+            features: vec!["with-foo".to_string()],
+            ..Features::default()
+        };
+        let classes = classify(
+            &lock,
+            Some(&app.workspace),
+            Some(&vendor),
+            None,
+            Some(&with_foo),
+            &mut diags,
+        );
+        assert_eq!(kind(&lock, &classes, "foo"), Some(DepKind::Normal));
+    }
+
+    #[test]
+    fn dependency_kinds() {
+        let app = App::new(
+            "kinds",
+            r#"
+[package]
+name = "app"
+version = "0.1.0"
+
+[dependencies]
+lib = "1"
+shared = "1"
+
+[build-dependencies]
+cc = "1"
+shared = "1"
+
+[dev-dependencies]
+tester = "1"
+"#,
+        );
+        let lock = lock(&[
+            ("app", &["lib", "shared", "cc", "tester"]),
+            ("lib", &["lib-tester"]),
+            ("lib-tester", &[]),
+            ("shared", &[]),
+            ("cc", &["jobserver"]),
+            ("jobserver", &[]),
+            ("tester", &[]),
+        ]);
+        let vendor = Vendor::from_files(&[
+            (
+                "lib/Cargo.toml",
+                "[package]\nname = \"lib\"\nversion = \"1.0.0\"\n\n\
+                 [dev-dependencies]\nlib-tester = \"1\"\n",
+            ),
+            (
+                "cc/Cargo.toml",
+                "[package]\nname = \"cc\"\nversion = \"1.0.0\"\n\n\
+                 [dependencies]\njobserver = \"1\"\n",
+            ),
+        ]);
+
+        let mut diags = Diagnostics::new(false);
+        let classes = classify(
+            &lock,
+            Some(&app.workspace),
+            Some(&vendor),
+            None,
+            None,
+            &mut diags,
+        );
+        assert_eq!(kind(&lock, &classes, "app"), Some(DepKind::Normal));
+        assert_eq!(kind(&lock, &classes, "lib"), Some(DepKind::Normal));
+        // Both a dependency and a build-dependency, so it is linked.
+        assert_eq!(kind(&lock, &classes, "shared"), Some(DepKind::Normal));
+        assert_eq!(kind(&lock, &classes, "cc"), Some(DepKind::Build));
+        // Normal dependencies of a build-dependency are only built too.
+        assert_eq!(kind(&lock, &classes, "jobserver"), Some(DepKind::Build));
+        assert_eq!(kind(&lock, &classes, "tester"), Some(DepKind::Dev));
+        // Dev-dependencies of anything but the workspace are never built.
+        assert_eq!(kind(&lock, &classes, "lib-tester"), None);
+    }
+}
@@ -0,0 +1,264 @@
+//! A machine readable JSON document describing a report, for tools that want
+//! more than the spec file tags.
+
+use crate::diagnostic::Level;
+use crate::lockfile::LockFile;
+use crate::report::Report;
+use crate::rpmver;
+use crate::vendor::Vendor;
+use serde_derive::Serialize;
+
+#[derive(Serialize)]
+struct Document<'a> {
+    packages: Vec<Package<'a>>,
+    libraries: Vec<Library<'a>>,
+    provides: Vec<String>,
+    license: Option<String>,
+    diagnostics: Vec<Diagnostic<'a>>,
+}
+
+#[derive(Serialize)]
+struct Package<'a> {
+    name: &'a str,
+    version: &'a str,
+    rpm_version: String,
+    source_kind: String,
+    source: Option<&'a str>,
+    checksum: Option<&'a str>,
+    /// Whether the package is listed in the provides.
+    bundled: bool,
+    /// normal, build or dev, if the dependency graph was walked.
+    dependency_kind: Option<String>,
+    vendored_path: Option<String>,
+    license: Option<String>,
+    license_source: String,
+}
+
+/// A native library bundled in one of the packages.
+#[derive(Serialize)]
+struct Library<'a> {
+    name: &'a str,
+    version: Option<&'a str>,
+    license: String,
+    package: PackageRef<'a>,
+}
+
+#[derive(Serialize)]
+struct Diagnostic<'a> {
+    level: &'static str,
+    package: Option<PackageRef<'a>>,
+    message: &'a str,
+}
+
+#[derive(Serialize)]
+struct PackageRef<'a> {
+    name: &'a str,
+    version: &'a str,
+}
+
+/// Render every locked package, along with what the report found out about
+/// it, as pretty printed JSON.
+pub fn render(lock: &LockFile, vendor: Option<&Vendor>, report: &Report) -> String {
+    let packages = lock
+        .packages
+        .iter()
+        .map(|pkg| {
+            let rpm_version = rpmver::from_semver(&pkg.version);
+            let bundled = report
+                .provides
+                .iter()
+                .any(|p| p.name == pkg.name && p.version == rpm_version);
+            let krate = report
+                .crates
+                .iter()
+                .find(|c| c.name == pkg.name && c.version == pkg.version);
+            Package {
+                name: &pkg.name,
+                version: &pkg.version,
+                rpm_version,
+                source_kind: pkg.kind().to_string(),
+                source: pkg.raw_source.as_deref(),
+                checksum: pkg.checksum.as_deref(),
+                bundled,
+                dependency_kind: report
+                    .kinds
+                    .get(&(pkg.name.clone(), pkg.version.clone()))
+                    .map(|k| k.to_string()),
+                vendored_path: krate
+                    .and_then(|c| vendor.map(|v| v.display(&c.dir).to_string_lossy().into_owned())),
+                license: krate
+                    .and_then(|c| c.license.as_ref())
+                    .map(|l| l.to_string()),
+                license_source: krate
+                    .map(|c| c.license_source.to_string())
+                    .unwrap_or_else(|| "unknown".to_string()),
+            }
+        })
+        .collect();
+
+    let diagnostics = report
+        .diagnostics
+        .iter()
+        .map(|d| Diagnostic {
+            level: match d.level {
+                Level::Debug => "debug",
+                Level::Warning => "warning",
+                Level::Error => "error",
+            },
+            package: d.package.as_ref().map(|p| PackageRef {
+                name: &p.name,
+                version: &p.version,
+            }),
+            message: &d.message,
+        })
+        .collect();
+
+    let libraries = report
+        .libraries
+        .iter()
+        .map(|l| Library {
+            name: &l.name,
+            version: l.version.as_deref(),
+            license: l.license.to_string(),
+            package: PackageRef {
+                name: &l.crate_name,
+                version: &l.crate_version,
+            },
+        })
+        .collect();
+
+    let doc = Document {
+        packages,
+        libraries,
+        provides: report.spec_provides(),
+        license: report.license.as_ref().map(|l| l.to_string()),
+        diagnostics,
+    };
+    serde_json::to_string_pretty(&doc).expect("Unable to serialise report")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::report::Options;
+    use serde_json::{json, Value};
+
+    #[test]
+    fn document() {
+        let lock = LockFile::parse(
+            br#"
+version = 4
+
+[[package]]
+name = "app"
+version = "0.1.0"
+dependencies = ["itoa", "missing", "ryu"]
+
+[[package]]
+name = "itoa"
+version = "1.0.11"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+checksum = "49f1f14873335454500d59611f1cf4a4b0f786f9ac11f4312a78e4cf2566695b"
+
+[[package]]
+name = "missing"
+version = "0.1.0-beta"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+
+[[package]]
+name = "ryu"
+version = "1.0.18"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+"#,
+        )
+        .unwrap();
+        let vendor = Vendor::from_files(&[
+            (
+                "itoa/Cargo.toml",
+                "[package]\nname = \"itoa\"\nversion = \"1.0.11\"\nlicense = \"MIT OR Apache-2.0\"\n",
+            ),
+            (
+                "ryu/Cargo.toml",
+                "[package]\nname = \"ryu\"\nversion = \"1.0.18\"\nlicense-file = \"LICENSE\"\n",
+            ),
+            ("ryu/LICENSE", include_str!("licenses/BSL-1.0.txt")),
+        ]);
+        let report = Report::generate(&lock, None, Some(&vendor), &Options::default());
+        let doc: Value = serde_json::from_str(&render(&lock, Some(&vendor), &report)).unwrap();
+
+        let keys: Vec<&String> = doc.as_object().unwrap().keys().collect();
+        assert_eq!(
+            keys,
+            [
+                "diagnostics",
+                "libraries",
+                "license",
+                "packages",
+                "provides"
+            ]
+        );
+        assert_eq!(doc["license"], "(Apache-2.0 OR MIT) AND BSL-1.0");
+        assert_eq!(
+            doc["provides"],
+            json!([
+                "bundled(crate(itoa)) = 1.0.11",
+                "bundled(crate(missing)) = 0.1.0~beta",
+                "bundled(crate(ryu)) = 1.0.18",
+            ])
+        );
+        assert_eq!(doc["libraries"], json!([]));
+
+        let packages = doc["packages"].as_array().unwrap();
+        assert_eq!(
+            packages[0],
+            json!({
+                "name": "app",
+                "version": "0.1.0",
+                "rpm_version": "0.1.0",
+                "source_kind": "local",
+                "source": null,
+                "checksum": null,
+                "bundled": false,
+                "dependency_kind": null,
+                "vendored_path": null,
+                "license": null,
+                "license_source": "unknown",
+            })
+        );
+        assert_eq!(
+            packages[1],
+            json!({
+                "name": "itoa",
+                "version": "1.0.11",
+                "rpm_version": "1.0.11",
+                "source_kind": "registry",
+                "source": "registry+https://github.com/rust-lang/crates.io-index",
+                "checksum": "49f1f14873335454500d59611f1cf4a4b0f786f9ac11f4312a78e4cf2566695b",
+                "bundled": true,
+                "dependency_kind": null,
+                "vendored_path": "vendor.tar:itoa",
+                "license": "Apache-2.0 OR MIT",
+                "license_source": "declared",
+            })
+        );
+        assert_eq!(packages[2]["bundled"], true);
+        assert_eq!(packages[2]["rpm_version"], "0.1.0~beta");
+        assert_eq!(packages[2]["vendored_path"], Value::Null);
+        assert_eq!(packages[2]["license_source"], "unknown");
+        assert_eq!(packages[3]["license"], "BSL-1.0");
+        assert_eq!(packages[3]["license_source"], "file");
+
+        let missing = doc["diagnostics"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|d| d["package"]["name"] == "missing")
+            .unwrap();
+        assert_eq!(missing["level"], "warning");
+        assert_eq!(
+            missing["package"],
+            json!({ "name": "missing", "version": "0.1.0-beta" })
+        );
+        assert!(missing["message"].as_str().unwrap().contains("vendor.tar"));
+    }
+}
@@ -0,0 +1,51 @@
+//! Generate RPM `Provides: bundled(crate(...))` and `License:` tags from a
+//! Cargo.lock and the matching vendored crates.
+//!
+//! ```no_run
+//! use cargo_lock2rpmprovides::{LockFile, Options, Report, Vendor, Workspace};
+//! use std::path::Path;
+//!
+//! let buffer = std::fs::read("Cargo.lock").unwrap();
+//! let lock = LockFile::parse(&buffer).unwrap();
+//! let workspace = Workspace::load(Path::new("."));
+//! let vendor = Vendor::open("vendor".into()).ok();
+//! let report = Report::generate(&lock, workspace.as_ref(), vendor.as_ref(), &Options::default());
+//! for provide in &report.provides {
+//!     println!("Provides: {}", provide);
+//! }
+//! ```
+
+pub mod auditable;
+pub mod changes;
+pub mod cyclonedx;
+pub mod detect;
+pub mod diagnostic;
+pub mod generator;
+pub mod graph;
+pub mod json;
+pub mod license;
+pub mod lockfile;
+pub mod manifest;
+pub mod native;
+pub mod policy;
+pub mod reconcile;
+pub mod report;
+pub mod rpmver;
+pub mod sbom;
+pub mod spdx;
+pub mod spdx_sbom;
+pub mod specfile;
+pub mod target;
+pub mod vendor;
+pub mod vendored;
+pub mod verify;
+pub mod workspace;
+
+pub use crate::diagnostic::{Diagnostic, Level};
+pub use crate::license::LicenseExpr;
+pub use crate::lockfile::{LockFile, LockedPackage};
+pub use crate::policy::Policy;
+pub use crate::report::{Options, Provide, Report};
+pub use crate::vendor::Vendor;
+pub use crate::vendored::{LicenseSource, VendoredCrate};
+pub use crate::workspace::Workspace;
@@ -0,0 +1,529 @@
+use crate::spdx;
+use std::fmt;
+
+/// A parsed SPDX license expression.
+///
+/// https://spdx.github.io/spdx-spec/v2.3/SPDX-license-expressions/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LicenseExpr {
+    License {
+        id: String,
+        /// The deprecated `+` suffix, meaning "or any later version".
+        plus: bool,
+        exception: Option<String>,
+    },
+    And(Vec<LicenseExpr>),
+    Or(Vec<LicenseExpr>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseError {
+    Empty,
+    UnexpectedEnd,
+    UnexpectedToken(String),
+    InvalidCharacter(char),
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseError::Empty => write!(f, "empty license expression"),
+            ParseError::UnexpectedEnd => write!(f, "license expression ended unexpectedly"),
+            ParseError::UnexpectedToken(t) => write!(f, "unexpected {:?} in license expression", t),
+            ParseError::InvalidCharacter(c) => {
+                write!(f, "invalid character {:?} in license expression", c)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Open,
+    Close,
+    And,
+    Or,
+    With,
+    Id(String),
+}
+
+fn is_id_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '-' || c == '.' || c == '+' || c == ':'
+}
+
+fn tokenise(s: &str) -> Result<Vec<Token>, ParseError> {
+    let mut tokens = Vec::new();
+    let mut chars = s.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '(' => {
+                chars.next();
+                tokens.push(Token::Open);
+            }
+            ')' => {
+                chars.next();
+                tokens.push(Token::Close);
+            }
+            // Cargo used to accept "MIT/Apache-2.0", which means OR.
+            '/' => {
+                chars.next();
+                tokens.push(Token::Or);
+            }
+            c if is_id_char(c) => {
+                let mut word = String::new();
+                while let Some(&c) = chars.peek() {
+                    if !is_id_char(c) {
+                        break;
+                    }
+                    word.push(c);
+                    chars.next();
+                }
+                // Operators must be whole words, so an id such as "ORACLE"
+                // or "GPL-2.0-or-later" is never mistaken for one.
+                tokens.push(match word.as_str() {
+                    "AND" | "and" => Token::And,
+                    "OR" | "or" => Token::Or,
+                    "WITH" | "with" => Token::With,
+                    _ => Token::Id(word),
+                });
+            }
+            c => return Err(ParseError::InvalidCharacter(c)),
+        }
+    }
+    Ok(tokens)
+}
+
+fn token_str(t: &Token) -> String {
+    match t {
+        Token::Open => "(".to_string(),
+        Token::Close => ")".to_string(),
+        Token::And => "AND".to_string(),
+        Token::Or => "OR".to_string(),
+        Token::With => "WITH".to_string(),
+        Token::Id(id) => id.clone(),
+    }
+}
+
+/// Recursive descent, with the SPDX precedence of WITH > AND > OR.
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let t = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        t
+    }
+
+    fn or(&mut self) -> Result<LicenseExpr, ParseError> {
+        let mut terms = vec![self.and()?];
+        while self.peek() == Some(&Token::Or) {
+            self.next();
+            terms.push(self.and()?);
+        }
+        Ok(if terms.len() == 1 {
+            terms.remove(0)
+        } else {
+            LicenseExpr::Or(terms)
+        })
+    }
+
+    fn and(&mut self) -> Result<LicenseExpr, ParseError> {
+        let mut terms = vec![self.primary()?];
+        while self.peek() == Some(&Token::And) {
+            self.next();
+            terms.push(self.primary()?);
+        }
+        Ok(if terms.len() == 1 {
+            terms.remove(0)
+        } else {
+            LicenseExpr::And(terms)
+        })
+    }
+
+    fn primary(&mut self) -> Result<LicenseExpr, ParseError> {
+        match self.next() {
+            Some(Token::Open) => {
+                let expr = self.or()?;
+                match self.next() {
+                    Some(Token::Close) => Ok(expr),
+                    Some(t) => Err(ParseError::UnexpectedToken(token_str(&t))),
+                    None => Err(ParseError::UnexpectedEnd),
+                }
+            }
+            Some(Token::Id(id)) => {
+                let (id, plus) = match id.strip_suffix('+') {
+                    Some(id) => (id.to_string(), true),
+                    None => (id, false),
+                };
+                let exception = if self.peek() == Some(&Token::With) {
+                    self.next();
+                    match self.next() {
+                        Some(Token::Id(e)) => Some(e),
+                        Some(t) => return Err(ParseError::UnexpectedToken(token_str(&t))),
+                        None => return Err(ParseError::UnexpectedEnd),
+                    }
+                } else {
+                    None
+                };
+                Ok(LicenseExpr::License {
+                    id,
+                    plus,
+                    exception,
+                })
+            }
+            Some(t) => Err(ParseError::UnexpectedToken(token_str(&t))),
+            None => Err(ParseError::UnexpectedEnd),
+        }
+    }
+}
+
+/// SPDX ids are matched case insensitively, but crates don't always use the
+/// canonical case. Unknown ids are kept as written.
+fn canonical_id(id: &str) -> String {
+    spdx::license(id)
+        .map(|(id, _)| id.to_string())
+        .unwrap_or_else(|| id.to_string())
+}
+
+fn canonical_exception(id: &str) -> String {
+    spdx::exception(id)
+        .map(|(id, _)| id.to_string())
+        .unwrap_or_else(|| id.to_string())
+}
+
+impl LicenseExpr {
+    pub fn parse(s: &str) -> Result<Self, ParseError> {
+        let tokens = tokenise(s)?;
+        if tokens.is_empty() {
+            return Err(ParseError::Empty);
+        }
+        let mut parser = Parser { tokens, pos: 0 };
+        let expr = parser.or()?;
+        match parser.next() {
+            None => Ok(expr.canonical()),
+            Some(t) => Err(ParseError::UnexpectedToken(token_str(&t))),
+        }
+    }
+
+    /// Combine expressions with AND, keeping each one as a distinct term.
+    pub fn all(mut exprs: Vec<LicenseExpr>) -> Option<Self> {
+        exprs.sort_by_cached_key(|e| e.to_string());
+        exprs.dedup();
+        match exprs.len() {
+            0 => None,
+            1 => exprs.pop(),
+            _ => Some(LicenseExpr::And(exprs)),
+        }
+    }
+
+    /// Normalise case, flatten nested operators of the same kind, and sort
+    /// and dedup operands, so that equivalent expressions compare equal.
+    fn canonical(self) -> Self {
+        match self {
+            LicenseExpr::License {
+                id,
+                plus,
+                exception,
+            } => LicenseExpr::License {
+                id: canonical_id(&id),
+                plus,
+                exception: exception.as_deref().map(canonical_exception),
+            },
+            LicenseExpr::And(terms) => Self::flatten(terms, true),
+            LicenseExpr::Or(terms) => Self::flatten(terms, false),
+        }
+    }
+
+    fn flatten(terms: Vec<LicenseExpr>, is_and: bool) -> Self {
+        let mut flat = Vec::new();
+        for term in terms.into_iter().map(LicenseExpr::canonical) {
+            match term {
+                LicenseExpr::And(inner) if is_and => flat.extend(inner),
+                LicenseExpr::Or(inner) if !is_and => flat.extend(inner),
+                other => flat.push(other),
+            }
+        }
+        flat.sort_by_cached_key(|e| e.to_string());
+        flat.dedup();
+        if flat.len() == 1 {
+            flat.remove(0)
+        } else if is_and {
+            LicenseExpr::And(flat)
+        } else {
+            LicenseExpr::Or(flat)
+        }
+    }
+
+    /// Reduce an expression to an equivalent, shorter one.
+    ///
+    /// Nested ANDs are flattened, and terms that are absorbed by others are
+    /// removed, so `MIT AND (Apache-2.0 OR MIT)` becomes `MIT`. If a list of
+    /// preferred licenses is given, each OR is replaced by the first operand
+    /// found in that list, which is how a packager records their choice.
+    pub fn simplify(&self, prefer: &[String]) -> Self {
+        let mut expr = self.clone().choose(prefer).canonical();
+        loop {
+            let next = expr.clone().absorb().canonical();
+            if next == expr {
+                return expr;
+            }
+            expr = next;
+        }
+    }
+
+    fn choose(self, prefer: &[String]) -> Self {
+        match self {
+            LicenseExpr::Or(terms) => {
+                let terms: Vec<_> = terms.into_iter().map(|t| t.choose(prefer)).collect();
+                let chosen = prefer.iter().find_map(|p| {
+                    terms
+                        .iter()
+                        .find(|t| t.to_string().eq_ignore_ascii_case(p))
+                        .cloned()
+                });
+                chosen.unwrap_or(LicenseExpr::Or(terms))
+            }
+            LicenseExpr::And(terms) => {
+                LicenseExpr::And(terms.into_iter().map(|t| t.choose(prefer)).collect())
+            }
+            lic => lic,
+        }
+    }
+
+    /// The operands of an OR, or of an AND, treating anything else as a
+    /// single operand.
+    fn operands(&self, of_and: bool) -> Vec<&LicenseExpr> {
+        match self {
+            LicenseExpr::And(terms) if of_and => terms.iter().collect(),
+            LicenseExpr::Or(terms) if !of_and => terms.iter().collect(),
+            other => vec![other],
+        }
+    }
+
+    /// Apply the absorption laws, `A AND (A OR B) = A` and
+    /// `A OR (A AND B) = A`, bottom up.
+    fn absorb(self) -> Self {
+        let (terms, is_and) = match self {
+            LicenseExpr::And(terms) => (terms, true),
+            LicenseExpr::Or(terms) => (terms, false),
+            lic => return lic,
+        };
+        let terms: Vec<_> = terms.into_iter().map(LicenseExpr::absorb).collect();
+
+        // Within an AND a term is redundant if another term's OR operands are
+        // a strict subset of its own, as satisfying the other satisfies it.
+        // Within an OR the same holds with the roles of the operators swapped.
+        let absorbed = |i: usize| {
+            let mine = terms[i].operands(!is_and);
+            terms.iter().enumerate().any(|(j, other)| {
+                let theirs = other.operands(!is_and);
+                j != i && theirs.len() < mine.len() && theirs.iter().all(|t| mine.contains(t))
+            })
+        };
+        let kept: Vec<_> = (0..terms.len())
+            .filter(|&i| !absorbed(i))
+            .map(|i| terms[i].clone())
+            .collect();
+
+        if is_and {
+            LicenseExpr::And(kept)
+        } else {
+            LicenseExpr::Or(kept)
+        }
+    }
+
+    fn fmt_term(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LicenseExpr::License { .. } => write!(f, "{}", self),
+            _ => write!(f, "({})", self),
+        }
+    }
+}
+
+impl fmt::Display for LicenseExpr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (terms, op) = match self {
+            LicenseExpr::License {
+                id,
+                plus,
+                exception,
+            } => {
+                write!(f, "{}", id)?;
+                if *plus {
+                    write!(f, "+")?;
+                }
+                if let Some(e) = exception {
+                    write!(f, " WITH {}", e)?;
+                }
+                return Ok(());
+            }
+            LicenseExpr::And(terms) => (terms, " AND "),
+            LicenseExpr::Or(terms) => (terms, " OR "),
+        };
+        for (i, term) in terms.iter().enumerate() {
+            if i > 0 {
+                write!(f, "{}", op)?;
+            }
+            term.fmt_term(f)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn canonical(s: &str) -> String {
+        LicenseExpr::parse(s).unwrap().to_string()
+    }
+
+    fn simplified(s: &str, prefer: &[&str]) -> String {
+        let prefer: Vec<String> = prefer.iter().map(|p| p.to_string()).collect();
+        LicenseExpr::parse(s).unwrap().simplify(&prefer).to_string()
+    }
+
+    #[test]
+    fn canonical_ordering() {
+        let cases = [
+            ("MIT OR Apache-2.0", "Apache-2.0 OR MIT"),
+            ("Apache-2.0 OR MIT", "Apache-2.0 OR MIT"),
+            ("MIT/Apache-2.0", "Apache-2.0 OR MIT"),
+            ("mit or apache-2.0", "Apache-2.0 OR MIT"),
+            ("MIT OR MIT", "MIT"),
+            (
+                "Zlib AND (MIT OR Apache-2.0)",
+                "(Apache-2.0 OR MIT) AND Zlib",
+            ),
+            ("(MIT OR BSD-3-Clause) OR ISC", "BSD-3-Clause OR ISC OR MIT"),
+            ("Unknown-License OR MIT", "MIT OR Unknown-License"),
+        ];
+        for (input, expected) in cases.iter() {
+            assert_eq!(canonical(input), *expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn precedence() {
+        // WITH binds tighter than AND, which binds tighter than OR.
+        assert_eq!(
+            LicenseExpr::parse("MIT OR Apache-2.0 AND Zlib").unwrap(),
+            LicenseExpr::parse("MIT OR (Apache-2.0 AND Zlib)").unwrap()
+        );
+        assert_eq!(
+            canonical("GPL-2.0-or-later WITH Classpath-exception-2.0 OR MIT"),
+            "GPL-2.0-or-later WITH Classpath-exception-2.0 OR MIT"
+        );
+        assert_eq!(
+            LicenseExpr::parse("Apache-2.0 with LLVM-exception").unwrap(),
+            LicenseExpr::License {
+                id: "Apache-2.0".to_string(),
+                plus: false,
+                exception: Some("LLVM-exception".to_string()),
+            }
+        );
+    }
+
+    #[test]
+    fn operators_are_whole_words() {
+        assert_eq!(canonical("ORACLE-Test"), "ORACLE-Test");
+        assert_eq!(canonical("GPL-2.0-or-later"), "GPL-2.0-or-later");
+        assert_eq!(
+            LicenseExpr::parse("LGPL-2.1+").unwrap(),
+            LicenseExpr::License {
+                id: "LGPL-2.1".to_string(),
+                plus: true,
+                exception: None,
+            }
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let cases = [
+            ("", ParseError::Empty),
+            ("   ", ParseError::Empty),
+            ("MIT OR", ParseError::UnexpectedEnd),
+            ("(MIT", ParseError::UnexpectedEnd),
+            ("MIT WITH", ParseError::UnexpectedEnd),
+            ("MIT)", ParseError::UnexpectedToken(")".to_string())),
+            ("AND MIT", ParseError::UnexpectedToken("AND".to_string())),
+            (
+                "MIT Apache-2.0",
+                ParseError::UnexpectedToken("Apache-2.0".to_string()),
+            ),
+            ("MIT,Apache-2.0", ParseError::InvalidCharacter(',')),
+        ];
+        for (input, expected) in cases.iter() {
+            assert_eq!(
+                LicenseExpr::parse(input),
+                Err(expected.clone()),
+                "{:?}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn absorption() {
+        assert_eq!(simplified("MIT AND (Apache-2.0 OR MIT)", &[]), "MIT");
+        assert_eq!(simplified("MIT OR (Apache-2.0 AND MIT)", &[]), "MIT");
+        assert_eq!(
+            simplified("(MIT OR Apache-2.0) AND (MIT OR Apache-2.0 OR Zlib)", &[]),
+            "Apache-2.0 OR MIT"
+        );
+        assert_eq!(
+            simplified("(MIT OR Apache-2.0) AND Zlib", &[]),
+            "(Apache-2.0 OR MIT) AND Zlib"
+        );
+    }
+
+    #[test]
+    fn prefer() {
+        assert_eq!(
+            simplified("MIT OR Apache-2.0", &["Apache-2.0"]),
+            "Apache-2.0"
+        );
+        assert_eq!(
+            simplified("MIT OR Apache-2.0", &["mit", "Apache-2.0"]),
+            "MIT"
+        );
+        assert_eq!(
+            simplified(
+                "(MIT OR Apache-2.0) AND (Zlib OR Apache-2.0)",
+                &["Apache-2.0"]
+            ),
+            "Apache-2.0"
+        );
+        // An OR without a preferred operand is left alone.
+        assert_eq!(
+            simplified("BSD-3-Clause OR ISC", &["MIT"]),
+            "BSD-3-Clause OR ISC"
+        );
+    }
+
+    #[test]
+    fn all_combines_with_and() {
+        let exprs = ["MIT", "Apache-2.0 OR MIT", "MIT"]
+            .iter()
+            .map(|s| LicenseExpr::parse(s).unwrap())
+            .collect();
+        assert_eq!(
+            LicenseExpr::all(exprs).unwrap().to_string(),
+            "(Apache-2.0 OR MIT) AND MIT"
+        );
+        assert_eq!(LicenseExpr::all(Vec::new()), None);
+    }
+}
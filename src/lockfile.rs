@@ -0,0 +1,507 @@
+use serde_derive::Deserialize;
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// The Cargo.lock format versions we know how to read.
+///
+/// V1 and V2 lockfiles do not carry a `version` key, so they are told apart
+/// by the presence of checksums in the `[metadata]` table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LockVersion {
+    V1,
+    V2,
+    V3,
+    V4,
+}
+
+impl fmt::Display for LockVersion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let v = match self {
+            LockVersion::V1 => 1,
+            LockVersion::V2 => 2,
+            LockVersion::V3 => 3,
+            LockVersion::V4 => 4,
+        };
+        write!(f, "{}", v)
+    }
+}
+
+#[derive(Debug)]
+pub enum LockError {
+    Toml(toml::de::Error),
+    UnsupportedVersion(i64),
+    InvalidDependency(String),
+}
+
+impl fmt::Display for LockError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LockError::Toml(e) => write!(f, "invalid toml - {}", e),
+            LockError::UnsupportedVersion(v) => write!(
+                f,
+                "lockfile version {} is not supported, only version = 3 or 4 is understood \
+                 (versions 1 and 2 have no version key)",
+                v
+            ),
+            LockError::InvalidDependency(d) => write!(f, "invalid dependency entry {:?}", d),
+        }
+    }
+}
+
+impl std::error::Error for LockError {}
+
+/// A coarse classification of where a package came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SourceKind {
+    /// Workspace members and path dependencies. These are not bundled code.
+    Local,
+    Registry,
+    Git,
+}
+
+impl fmt::Display for SourceKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SourceKind::Local => write!(f, "local"),
+            SourceKind::Registry => write!(f, "registry"),
+            SourceKind::Git => write!(f, "git"),
+        }
+    }
+}
+
+/// Where a locked package came from. Packages without a source are path
+/// dependencies or workspace members.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Source {
+    /// A registry index, such as crates.io. `sparse+` indexes are included here.
+    Registry(String),
+    /// A git repository. Query parameters (branch, tag, rev) are percent decoded.
+    Git {
+        url: String,
+        query: Vec<(String, String)>,
+        precise: Option<String>,
+    },
+    /// Anything else we don't recognise, kept verbatim.
+    Other(String),
+}
+
+impl Source {
+    pub fn kind(&self) -> SourceKind {
+        match self {
+            Source::Git { .. } => SourceKind::Git,
+            // Other sources are registry replacements such as local-registry+
+            // or directory+, which still contain third party crates.
+            Source::Registry(_) | Source::Other(_) => SourceKind::Registry,
+        }
+    }
+
+    pub(crate) fn parse(s: &str) -> Self {
+        if let Some(url) = s.strip_prefix("registry+") {
+            Source::Registry(url.to_string())
+        } else if s.starts_with("sparse+") {
+            Source::Registry(s.to_string())
+        } else if let Some(rest) = s.strip_prefix("git+") {
+            let (rest, precise) = match rest.split_once('#') {
+                Some((r, p)) => (r, Some(p.to_string())),
+                None => (rest, None),
+            };
+            let (url, query) = match rest.split_once('?') {
+                Some((u, q)) => (
+                    u.to_string(),
+                    q.split('&')
+                        .filter(|kv| !kv.is_empty())
+                        .map(|kv| match kv.split_once('=') {
+                            Some((k, v)) => (percent_decode(k), percent_decode(v)),
+                            None => (percent_decode(kv), String::new()),
+                        })
+                        .collect(),
+                ),
+                None => (rest.to_string(), Vec::new()),
+            };
+            Source::Git {
+                url,
+                query,
+                precise,
+            }
+        } else {
+            Source::Other(s.to_string())
+        }
+    }
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Source::Registry(url) => write!(f, "registry {}", url),
+            Source::Git {
+                url,
+                query,
+                precise,
+            } => {
+                write!(f, "git {}", url)?;
+                for (k, v) in query {
+                    write!(f, " {}={}", k, v)?;
+                }
+                if let Some(p) = precise {
+                    write!(f, " @ {}", p)?;
+                }
+                Ok(())
+            }
+            Source::Other(s) => write!(f, "{}", s),
+        }
+    }
+}
+
+/// v4 lockfiles percent encode the git query parameters, older versions
+/// write them out verbatim. Decoding is a no-op for the older formats.
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            if let Some(b) = s
+                .get(i + 1..i + 3)
+                .and_then(|h| u8::from_str_radix(h, 16).ok())
+            {
+                out.push(b);
+                i += 3;
+                continue;
+            }
+        }
+        out.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// A reference from one package to another, as written in `dependencies`.
+///
+/// v1 always writes "name version (source)", later versions only write as
+/// much as is needed to be unambiguous, so version and source are optional.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Dependency {
+    pub name: String,
+    pub version: Option<String>,
+    pub source: Option<String>,
+}
+
+impl Dependency {
+    fn parse(s: &str) -> Result<Self, LockError> {
+        let mut parts = s.splitn(3, ' ');
+        let name = match parts.next() {
+            Some(n) if !n.is_empty() => n.to_string(),
+            _ => return Err(LockError::InvalidDependency(s.to_string())),
+        };
+        let version = parts.next().map(str::to_string);
+        let source = match parts.next() {
+            Some(src) => match src.strip_prefix('(').and_then(|s| s.strip_suffix(')')) {
+                Some(src) => Some(src.to_string()),
+                None => return Err(LockError::InvalidDependency(s.to_string())),
+            },
+            None => None,
+        };
+        Ok(Dependency {
+            name,
+            version,
+            source,
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct LockedPackage {
+    pub name: String,
+    pub version: String,
+    pub source: Option<Source>,
+    /// The source as written in the lockfile, used to match v1 dependency strings.
+    pub raw_source: Option<String>,
+    pub checksum: Option<String>,
+    pub dependencies: Vec<Dependency>,
+}
+
+impl LockedPackage {
+    pub fn kind(&self) -> SourceKind {
+        self.source
+            .as_ref()
+            .map(Source::kind)
+            .unwrap_or(SourceKind::Local)
+    }
+
+    /// Does this package satisfy a dependency reference?
+    pub fn matches(&self, dep: &Dependency) -> bool {
+        self.name == dep.name
+            && dep.version.as_ref().is_none_or(|v| v == &self.version)
+            && dep
+                .source
+                .as_ref()
+                .is_none_or(|s| Some(s) == self.raw_source.as_ref())
+    }
+}
+
+#[derive(Debug)]
+pub struct LockFile {
+    pub version: LockVersion,
+    pub packages: Vec<LockedPackage>,
+}
+
+#[derive(Deserialize, Debug)]
+struct Pkg {
+    name: String,
+    version: String,
+    dependencies: Option<Vec<String>>,
+    source: Option<String>,
+    checksum: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Config {
+    version: Option<i64>,
+    /// Very old v1 lockfiles list the root package separately.
+    root: Option<Pkg>,
+    package: Option<Vec<Pkg>>,
+    /// v1 stores checksums here, keyed by "checksum name version (source)".
+    metadata: Option<BTreeMap<String, toml::Value>>,
+}
+
+impl LockFile {
+    pub fn parse(buffer: &[u8]) -> Result<Self, LockError> {
+        let config: Config = toml::from_slice(buffer).map_err(LockError::Toml)?;
+
+        let metadata_checksums: BTreeMap<String, String> = config
+            .metadata
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|(k, v)| {
+                let k = k.strip_prefix("checksum ")?.to_string();
+                match v {
+                    toml::Value::String(s) if s != "<none>" => Some((k, s)),
+                    _ => None,
+                }
+            })
+            .collect();
+
+        let version = match config.version {
+            None if metadata_checksums.is_empty() => {
+                // Without checksums we can't distinguish a v1 lock of only path
+                // dependencies from v2, but they parse identically.
+                LockVersion::V2
+            }
+            None => LockVersion::V1,
+            Some(3) => LockVersion::V3,
+            Some(4) => LockVersion::V4,
+            Some(v) => return Err(LockError::UnsupportedVersion(v)),
+        };
+
+        let packages = config
+            .root
+            .into_iter()
+            .chain(config.package.unwrap_or_default())
+            .map(|pkg| {
+                let dependencies = pkg
+                    .dependencies
+                    .unwrap_or_default()
+                    .iter()
+                    .map(|d| Dependency::parse(d))
+                    .collect::<Result<Vec<_>, _>>()?;
+
+                let key = match &pkg.source {
+                    Some(src) => format!("{} {} ({})", pkg.name, pkg.version, src),
+                    None => format!("{} {}", pkg.name, pkg.version),
+                };
+                let checksum = pkg
+                    .checksum
+                    .or_else(|| metadata_checksums.get(&key).cloned());
+
+                Ok(LockedPackage {
+                    source: pkg.source.as_deref().map(Source::parse),
+                    raw_source: pkg.source,
+                    name: pkg.name,
+                    version: pkg.version,
+                    checksum,
+                    dependencies,
+                })
+            })
+            .collect::<Result<Vec<_>, LockError>>()?;
+
+        Ok(LockFile { version, packages })
+    }
+
+    /// Resolve a dependency reference to the locked package it names.
+    pub fn resolve(&self, dep: &Dependency) -> Option<&LockedPackage> {
+        self.packages.iter().find(|pkg| pkg.matches(dep))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CRATES_IO: &str = "registry+https://github.com/rust-lang/crates.io-index";
+
+    #[test]
+    fn v1_with_root_and_metadata_checksums() {
+        let lock = LockFile::parse(
+            br#"
+[root]
+name = "app"
+version = "0.1.0"
+dependencies = [
+ "itoa 1.0.11 (registry+https://github.com/rust-lang/crates.io-index)",
+]
+
+[[package]]
+name = "itoa"
+version = "1.0.11"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+
+[metadata]
+"checksum itoa 1.0.11 (registry+https://github.com/rust-lang/crates.io-index)" = "49f1f14873335454500d59611f1cf4a4b0f786f9ac11f4312a78e4cf2566695b"
+"#,
+        )
+        .unwrap();
+        assert_eq!(lock.version, LockVersion::V1);
+        assert_eq!(lock.packages.len(), 2);
+
+        let app = &lock.packages[0];
+        assert_eq!(app.name, "app");
+        assert_eq!(app.kind(), SourceKind::Local);
+        assert_eq!(
+            app.dependencies,
+            [Dependency {
+                name: "itoa".to_string(),
+                version: Some("1.0.11".to_string()),
+                source: Some(CRATES_IO.to_string()),
+            }]
+        );
+
+        let itoa = lock.resolve(&app.dependencies[0]).unwrap();
+        assert_eq!(itoa.kind(), SourceKind::Registry);
+        assert_eq!(
+            itoa.checksum.as_deref(),
+            Some("49f1f14873335454500d59611f1cf4a4b0f786f9ac11f4312a78e4cf2566695b")
+        );
+    }
+
+    #[test]
+    fn v2_has_no_version_key() {
+        let lock = LockFile::parse(
+            br#"
+[[package]]
+name = "app"
+version = "0.1.0"
+dependencies = ["itoa"]
+
+[[package]]
+name = "itoa"
+version = "1.0.11"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+checksum = "49f1f14873335454500d59611f1cf4a4b0f786f9ac11f4312a78e4cf2566695b"
+"#,
+        )
+        .unwrap();
+        assert_eq!(lock.version, LockVersion::V2);
+        assert_eq!(lock.packages[0].dependencies[0].version, None);
+        assert_eq!(
+            lock.resolve(&lock.packages[0].dependencies[0])
+                .map(|p| p.name.as_str()),
+            Some("itoa")
+        );
+        assert!(lock.packages[1].checksum.is_some());
+    }
+
+    #[test]
+    fn v3_git_query_is_verbatim() {
+        let lock = LockFile::parse(
+            br#"
+version = 3
+
+[[package]]
+name = "foo"
+version = "0.2.0"
+source = "git+https://example.com/foo.git?branch=dev#0123456789abcdef"
+"#,
+        )
+        .unwrap();
+        assert_eq!(lock.version, LockVersion::V3);
+        assert_eq!(lock.packages[0].kind(), SourceKind::Git);
+        assert_eq!(
+            lock.packages[0].source,
+            Some(Source::Git {
+                url: "https://example.com/foo.git".to_string(),
+                query: vec![("branch".to_string(), "dev".to_string())],
+                precise: Some("0123456789abcdef".to_string()),
+            })
+        );
+    }
+
+    #[test]
+    fn v4_git_query_is_percent_decoded() {
+        let lock = LockFile::parse(
+            br#"
+version = 4
+
+[[package]]
+name = "foo"
+version = "0.2.0"
+source = "git+https://example.com/foo.git?branch=feature%2Fnew&rev=v1%2B2#0123456789abcdef"
+
+[[package]]
+name = "bar"
+version = "1.0.0"
+source = "sparse+https://index.crates.io/"
+"#,
+        )
+        .unwrap();
+        assert_eq!(lock.version, LockVersion::V4);
+        match &lock.packages[0].source {
+            Some(Source::Git { query, .. }) => assert_eq!(
+                query,
+                &[
+                    ("branch".to_string(), "feature/new".to_string()),
+                    ("rev".to_string(), "v1+2".to_string()),
+                ]
+            ),
+            other => panic!("not a git source: {:?}", other),
+        }
+        assert_eq!(
+            lock.packages[1].source,
+            Some(Source::Registry(
+                "sparse+https://index.crates.io/".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn percent_decoding_leaves_invalid_escapes_alone() {
+        assert_eq!(percent_decode("a%2Fb"), "a/b");
+        assert_eq!(percent_decode("100%"), "100%");
+        assert_eq!(percent_decode("%zz"), "%zz");
+    }
+
+    #[test]
+    fn explicit_old_versions_are_rejected() {
+        for v in [1, 2, 5].iter() {
+            let text = format!("version = {}\n", v);
+            match LockFile::parse(text.as_bytes()) {
+                Err(e @ LockError::UnsupportedVersion(_)) => {
+                    assert!(e.to_string().contains("version = 3 or 4"), "{}", e)
+                }
+                other => panic!("version {} parsed as {:?}", v, other),
+            }
+        }
+    }
+
+    #[test]
+    fn invalid_dependency() {
+        let err = LockFile::parse(
+            br#"
+[[package]]
+name = "app"
+version = "0.1.0"
+dependencies = ["itoa 1.0.11 crates.io"]
+"#,
+        );
+        assert!(matches!(err, Err(LockError::InvalidDependency(_))));
+    }
+}
@@ -1,204 +1,587 @@
-use serde_derive::Deserialize;
+use cargo_lock2rpmprovides::generator::Generator;
+use cargo_lock2rpmprovides::graph::Features;
+use cargo_lock2rpmprovides::manifest::DepKind;
+use cargo_lock2rpmprovides::sbom::{self, Sbom};
+use cargo_lock2rpmprovides::target::{self, Target};
+use cargo_lock2rpmprovides::{
+    auditable, changes, cyclonedx, json, spdx_sbom, specfile, LockFile, Options, Policy, Report,
+    Vendor, Workspace,
+};
 use std::env;
-use std::path::Path;
-use std::path::PathBuf;
+use std::io::{self, BufRead};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 use structopt::StructOpt;
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Spec,
+    Json,
+    Spdx,
+    SpdxJson,
+    Spdx3Json,
+    CycloneDxJson,
+    CycloneDxXml,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "spec" => Ok(Format::Spec),
+            "json" => Ok(Format::Json),
+            "spdx" => Ok(Format::Spdx),
+            "spdx-json" => Ok(Format::SpdxJson),
+            "spdx3-json" => Ok(Format::Spdx3Json),
+            "cyclonedx-json" => Ok(Format::CycloneDxJson),
+            "cyclonedx-xml" => Ok(Format::CycloneDxXml),
+            _ => Err(format!(
+                "unknown format {:?}, expected spec, json, spdx, spdx-json, spdx3-json, \
+                 cyclonedx-json or cyclonedx-xml",
+                s
+            )),
+        }
+    }
+}
+
 #[derive(Debug, StructOpt)]
 struct Opt {
     #[structopt(short, long)]
     debug: bool,
+    #[structopt(long)]
+    /// Include workspace members and path dependencies, which are not bundled.
+    include_local: bool,
+    #[structopt(long)]
+    /// Simplify the combined License expression, removing redundant terms.
+    simplify: bool,
+    #[structopt(long, use_delimiter = true)]
+    /// Licenses to choose from each OR, in order of preference, such as
+    /// "MIT,Apache-2.0". Implies --simplify.
+    prefer: Vec<String>,
+    #[structopt(long)]
+    /// Check each crate's license against the SPDX license list, and fail
+    /// if any are invalid.
+    validate: bool,
+    #[structopt(long, parse(from_os_str))]
+    /// A file of allowed licenses, one per line. Implies --validate.
+    allow_list: Option<PathBuf>,
+    #[structopt(long, parse(from_os_str))]
+    /// A file of denied licenses, one per line. Implies --validate.
+    deny_list: Option<PathBuf>,
+    #[structopt(long)]
+    /// Fail if a crate's license can't be determined. Implies --validate.
+    deny_unknown_licenses: bool,
+    #[structopt(long, default_value = "spec")]
+    /// The output format: spec for Provides and License tags, json for a
+    /// report of every locked package, or an SBOM as spdx (2.3 tag-value),
+    /// spdx-json (2.3), spdx3-json (3.0 JSON-LD), cyclonedx-json or
+    /// cyclonedx-xml (1.5).
+    format: Format,
+    #[structopt(long)]
+    /// Check that the vendored crates match the checksums in Cargo.lock, and
+    /// fail if any are stale or missing.
+    verify: bool,
+    #[structopt(long)]
+    /// Also hash every file of the vendored crates, to find any that were
+    /// modified. Implies --verify.
+    verify_files: bool,
+    #[structopt(long)]
+    /// Report vendored crates that aren't in Cargo.lock, and locked crates
+    /// that aren't vendored or are vendored at a different version.
+    reconcile: bool,
+    #[structopt(long)]
+    /// Fail if Cargo.lock and the vendor tree differ at all. Implies
+    /// --reconcile.
+    strict: bool,
+    #[structopt(long)]
+    /// Only include the crates that are built for the target, following
+    /// the [target.'cfg(...)'.dependencies] of each Cargo.toml from the
+    /// workspace members.
+    target_filter: bool,
+    #[structopt(long, value_name = "triple")]
+    /// The Linux target triple to filter for, such as
+    /// aarch64-unknown-linux-gnu or %{_target_platform}. Defaults to
+    /// x86_64-unknown-linux-gnu. Implies --target-filter.
+    target: Option<String>,
+    #[structopt(long)]
+    /// Only include the crates used by the default features of the
+    /// workspace, leaving out optional dependencies nothing enables.
+    feature_filter: bool,
+    #[structopt(long, use_delimiter = true)]
+    /// Features to enable, as for cargo build. Implies --feature-filter.
+    features: Vec<String>,
+    #[structopt(long)]
+    /// Enable every feature of the workspace members. Implies
+    /// --feature-filter.
+    all_features: bool,
+    #[structopt(long)]
+    /// Don't enable the default features of the workspace members. Implies
+    /// --feature-filter.
+    no_default_features: bool,
+    #[structopt(long, use_delimiter = true, value_name = "kinds")]
+    /// Only provide the crates that are needed as these kinds of dependency,
+    /// such as "normal,build". A crate is normal if the workspace needs it
+    /// through normal dependencies alone, build if only through a
+    /// build-dependency, and dev if only through a dev-dependency.
+    provide_kinds: Vec<DepKind>,
+    #[structopt(long, use_delimiter = true, value_name = "kinds")]
+    /// Only include the licenses of the crates that are needed as these
+    /// kinds of dependency, such as "normal", in the License tag.
+    license_kinds: Vec<DepKind>,
+    #[structopt(long, parse(from_os_str))]
+    /// Rewrite the provides between the "# BEGIN cargo-lock2rpmprovides" and
+    /// "# END cargo-lock2rpmprovides" lines of this spec file, instead of
+    /// printing them.
+    update_spec: Option<PathBuf>,
+    #[structopt(long, requires = "update-spec")]
+    /// Also rewrite the License tag of the main package in the spec file.
+    update_license: bool,
+    #[structopt(long, parse(from_os_str), conflicts_with = "update-spec")]
+    /// Check that the provides and License tag of this spec file are up to
+    /// date, printing a diff and failing if they aren't.
+    check: Option<PathBuf>,
+    #[structopt(long, parse(from_os_str), number_of_values = 2, value_names = &["old", "new"])]
+    /// Compare two lockfiles, and print a changelog entry for the bundled
+    /// crates that were added, removed, updated or changed license.
+    changes: Vec<PathBuf>,
+    #[structopt(long, parse(from_os_str), requires = "changes")]
+    /// The vendor directory or tarball of the old lockfile, to report the
+    /// crates whose license changed.
+    old_vendordir: Option<PathBuf>,
+    #[structopt(long, requires = "changes")]
+    /// Print the changes as an RPM %changelog entry, rather than for an
+    /// openSUSE .changes file.
+    rpm_changelog: bool,
+    #[structopt(long)]
+    /// Run as an RPM dependency generator: read the paths of installed files
+    /// on stdin, and print the bundled provides of each Rust binary among
+    /// them. Binaries built without cargo auditable fall back to the
+    /// Cargo.lock of the working directory, if one is given.
+    rpm_generator: bool,
+    #[structopt(long, parse(from_os_str))]
+    /// Read the dependencies that cargo auditable embedded in this binary,
+    /// instead of Cargo.lock.
+    binary: Option<PathBuf>,
     #[structopt(parse(from_os_str))]
     _dummy: PathBuf,
     #[structopt(parse(from_os_str))]
     /// The directory containing the Cargo.toml.
     workdir: Option<PathBuf>,
     #[structopt(parse(from_os_str))]
-    /// The path to the associated vendor directory.
+    /// The path to the associated vendor directory, or a vendor tarball
+    /// (.tar, .tar.gz, .tar.xz or .tar.zst).
     vendordir: Option<PathBuf>,
 }
 
-#[derive(Deserialize, Debug)]
-struct Pkg {
-    name: String,
-    version: String,
-    dependencies: Option<Vec<String>>,
-    source: Option<String>,
-    checksum: Option<String>,
-}
-
-#[derive(Deserialize)]
-struct Config {
-    package: Vec<Pkg>,
-}
+fn read_lock(lockfile: &Path, debug: bool) -> LockFile {
+    // Do we have the Cargo.lock?
+    if !lockfile.exists() {
+        eprintln!("lockfile {:?} not found", lockfile);
+        std::process::exit(1);
+    } else if debug {
+        eprintln!("DEBUG -> found {:?}", lockfile);
+    }
 
-#[derive(Debug, Deserialize)]
-struct CargoPkg {
-    license: Option<String>,
-    #[serde(rename = "license-file")]
-    license_file: Option<String>,
-}
+    // Can we parse it?
+    let buffer = std::fs::read(lockfile).expect("Unable to open lockfile for reading!");
 
-#[derive(Debug, Deserialize)]
-struct Cargotoml {
-    package: CargoPkg,
-}
+    let lock = match LockFile::parse(&buffer) {
+        Ok(lock) => lock,
+        Err(e) => {
+            eprintln!("Unable to parse lockfile {:?} - {}", lockfile, e);
+            std::process::exit(1);
+        }
+    };
 
-fn do_license_check(vendordir: &Path, debug: bool) -> Option<String> {
-    let name = vendordir.join("Cargo.toml");
-    // https://doc.rust-lang.org/cargo/reference/manifest.html#the-license-and-license-file-fields
     if debug {
-        eprintln!("checking license in ... {:?}", name);
+        eprintln!("DEBUG -> lockfile version {}", lock.version);
     }
-    if !name.exists() {
-        eprintln!(
-            "Unable to check license from {:?}. You may need to check this manually",
-            name
-        );
-        return None;
+    lock
+}
+
+fn open_vendor(vendordir: &Path, all_files: bool, debug: bool) -> Option<Vendor> {
+    if vendordir.exists() {
+        if debug {
+            eprintln!("DEBUG -> found {:?}", vendordir);
+        }
+        let vendor = if all_files {
+            Vendor::open_all(vendordir.to_path_buf())
+        } else {
+            Vendor::open(vendordir.to_path_buf())
+        };
+        match vendor {
+            Ok(vendor) => Some(vendor),
+            Err(e) => {
+                eprintln!("ERROR could not read vendor - {:?} - {}", vendordir, e);
+                None
+            }
+        }
+    } else {
+        eprintln!("ERROR could not find vendordir - {:?}", vendordir);
+        None
     }
+}
 
-    let buffer = std::fs::read(&name).expect("Unable to open cargo.toml for reading!");
+fn read_binary(binary: &Path, debug: bool) -> LockFile {
+    let buffer = match std::fs::read(binary) {
+        Ok(buffer) => buffer,
+        Err(e) => {
+            eprintln!("Unable to read binary {:?} - {}", binary, e);
+            std::process::exit(1);
+        }
+    };
 
-    let config: Cargotoml =
-        toml::from_slice(&buffer).expect("Unable to parse cargo.toml, invalid!");
+    let lock = match auditable::read(&buffer) {
+        Ok(lock) => lock,
+        Err(e) => {
+            eprintln!("Unable to read dependencies from {:?} - {}", binary, e);
+            std::process::exit(1);
+        }
+    };
 
     if debug {
-        eprintln!("Parsed config - {:?}", config);
+        eprintln!(
+            "DEBUG -> found {} packages in {:?}",
+            lock.packages.len(),
+            binary
+        );
     }
+    lock
+}
 
-    match (config.package.license, config.package.license_file) {
-        (Some(lic), _) => {
-            // We have to do a bit of normalisation here.
-            // If it contains an operator, we need braces.
-            let mut lic = lic.replace(" / ", " OR ")
-                .replace("/", " OR ");
-
-            if lic.contains("OR") || lic.contains("AND") {
-                lic.insert_str(0, "( ");
-                lic.push_str(" )");
+/// Print the provides of each file named on stdin, as rpm's fileattrs
+/// generators do. Diagnostics are only shown with --debug, as anything on
+/// stderr ends up in the build log of every package.
+fn rpm_generator(lockdir: Option<&Path>, vendordir: &Path, options: &Options) {
+    let mut generator = Generator::new(lockdir, vendordir, options);
+    let stdin = io::stdin();
+    for line in stdin.lock().lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(e) => {
+                eprintln!("Unable to read file list - {}", e);
+                std::process::exit(1);
             }
-
-            // Some common replacements to avoid duplication.
-            match lic.as_str() {
-                "( MIT OR Apache-2.0 )" => Some("( Apache-2.0 OR MIT )".to_string()),
-                _ => Some(lic),
+        };
+        let file = Path::new(line.trim());
+        if file.as_os_str().is_empty() {
+            continue;
+        }
+        let (provides, diagnostics) = match generator.provides(file) {
+            Ok(found) => found,
+            Err(e) => {
+                eprintln!("ERROR {}", e);
+                std::process::exit(1);
+            }
+        };
+        if options.debug {
+            for diag in diagnostics {
+                eprintln!("{}", diag);
             }
-
-
         }
-        (None, Some(fname)) => {
-            let license_file = vendordir.join(fname);
-            eprintln!(
-                "Unable to find license in {:?}. You may need to check {:?} for details.",
-                name, license_file
-            );
-            None
+        for provide in provides {
+            println!("{}", provide);
         }
-        (None, None) => {
+    }
+}
+
+fn print_changes(
+    old: &Path,
+    new: &Path,
+    old_vendor: Option<&Vendor>,
+    vendor: Option<&Vendor>,
+    rpm_changelog: bool,
+    debug: bool,
+) {
+    let old = read_lock(old, debug);
+    let new = read_lock(new, debug);
+
+    // Only the licenses are needed from the vendored crates.
+    let options = Options {
+        debug,
+        ..Options::default()
+    };
+    let old_report = Report::generate(&old, None, old_vendor, &options);
+    let report = Report::generate(&new, None, vendor, &options);
+    for diag in old_report.diagnostics.iter().chain(&report.diagnostics) {
+        eprintln!("{}", diag);
+    }
+
+    let changes = changes::compare(&old, &new, &old_report.crates, &report.crates);
+    if changes.is_empty() {
+        eprintln!("No changes to the bundled crates");
+        return;
+    }
+    let entry = if rpm_changelog {
+        let packager = changes::packager().unwrap_or_else(|| {
             eprintln!(
-                "Unable to determine license for {:?}. You must manually investigate!",
-                name
+                "Unable to sign the %changelog entry, set RPM_PACKAGER, %packager in \
+                 ~/.rpmmacros, email in the osc config, or MAILADDR"
             );
-            None
-        }
+            std::process::exit(1);
+        });
+        changes::rpm_changelog(&changes, sbom::build_time(), &packager)
+    } else {
+        changes::changes_entry(&changes)
+    };
+    if let Some(entry) = entry {
+        print!("{}", entry);
     }
 }
 
 fn main() {
     let opt = Opt::from_args();
 
+    let explicit_workdir = opt.workdir.is_some();
     let path = opt
         .workdir
         .unwrap_or_else(|| env::current_dir().expect("Unable to locate current work dir"));
 
-    let vendordir = opt
-        .vendordir
-        .map(|s| PathBuf::from(s))
-        .unwrap_or_else(|| path.join("vendor"));
+    let vendordir = opt.vendordir.unwrap_or_else(|| {
+        // Prefer an unpacked vendor dir, but fall back to the tarballs that
+        // obs-service-cargo_vendor leaves next to the sources.
+        ["vendor", "vendor.tar.zst", "vendor.tar.xz", "vendor.tar.gz"]
+            .iter()
+            .map(|name| path.join(name))
+            .find(|p| p.exists())
+            .unwrap_or_else(|| path.join("vendor"))
+    });
 
     if opt.debug {
         eprintln!("DEBUG -> working dir {:?}", path);
         eprintln!("DEBUG -> vendor dir {:?}", vendordir);
     }
 
-    // Do we have the Cargo.lock?
-    let lockfile = path.join("Cargo.lock");
-    if !lockfile.exists() {
-        eprintln!("lockfile {:?} not found", lockfile);
-        std::process::exit(1);
-    } else if opt.debug {
-        eprintln!("DEBUG -> found {:?}", lockfile);
+    if let [old, new] = opt.changes.as_slice() {
+        let old_vendor = match &opt.old_vendordir {
+            Some(dir) => open_vendor(dir, false, opt.debug),
+            None => None,
+        };
+        let vendor = open_vendor(&vendordir, false, opt.debug);
+        print_changes(
+            old,
+            new,
+            old_vendor.as_ref(),
+            vendor.as_ref(),
+            opt.rpm_changelog,
+            opt.debug,
+        );
+        return;
     }
 
-    // Can we parse it?
-    let buffer = std::fs::read(lockfile).expect("Unable to open lockfile for reading!");
-
-    let config: Config =
-        toml::from_slice(&buffer).expect("Unable to parse lockfile, invalid toml!");
+    let policy = if opt.validate
+        || opt.allow_list.is_some()
+        || opt.deny_list.is_some()
+        || opt.deny_unknown_licenses
+    {
+        match Policy::load(opt.allow_list.as_deref(), opt.deny_list.as_deref()) {
+            Ok(policy) => Some(policy),
+            Err(e) => {
+                eprintln!("Unable to read license policy - {}", e);
+                std::process::exit(1);
+            }
+        }
+    } else {
+        None
+    };
 
-    // Now check the licenses if possible.
-    let debug = opt.debug;
-    let mut licenses: Vec<String> = if vendordir.exists() {
-        if debug {
-            eprintln!("DEBUG -> found {:?}", vendordir);
+    let target = match (&opt.target, opt.target_filter) {
+        (None, false) => None,
+        (triple, _) => {
+            let triple = triple.as_deref().unwrap_or(target::DEFAULT_TRIPLE);
+            match Target::from_triple(triple) {
+                Some(target) => Some(target),
+                None => {
+                    eprintln!(
+                        "Unknown target {:?}, expected a Linux triple such as {}",
+                        triple,
+                        target::TRIPLES.join(", ")
+                    );
+                    std::process::exit(1);
+                }
+            }
         }
-        config
-            .package
-            .iter()
-            .filter_map(|pkg| {
-                let pkg_vendored_path = vendordir.join(&pkg.name);
-                do_license_check(&pkg_vendored_path, debug)
-            })
-            .collect()
+    };
+
+    let features = if opt.feature_filter
+        || !opt.features.is_empty()
+        || opt.all_features
+        || opt.no_default_features
+    {
+        Some(Features {
+            features: opt.features,
+            all_features: opt.all_features,
+            no_default_features: opt.no_default_features,
+        })
     } else {
-        eprintln!("ERROR could not find vendordir - {:?}", vendordir);
-        Vec::new()
+        None
     };
 
-    licenses.sort();
-    licenses.dedup();
+    let options = Options {
+        debug: opt.debug,
+        include_local: opt.include_local,
+        simplify: opt.simplify,
+        prefer: opt.prefer,
+        policy,
+        verify: opt.verify,
+        verify_files: opt.verify_files,
+        reconcile: opt.reconcile,
+        strict: opt.strict,
+        deny_unknown_licenses: opt.deny_unknown_licenses,
+        target,
+        features,
+        provide_kinds: opt.provide_kinds,
+        license_kinds: opt.license_kinds,
+    };
+
+    if opt.rpm_generator {
+        let lockdir = if explicit_workdir {
+            Some(path.as_path())
+        } else {
+            None
+        };
+        rpm_generator(lockdir, &vendordir, &options);
+        return;
+    }
+
+    // A binary carries no workspace, its root package is the one built.
+    let (lock, workspace) = match &opt.binary {
+        Some(binary) => (read_binary(binary, opt.debug), None),
+        None => (
+            read_lock(&path.join("Cargo.lock"), opt.debug),
+            Workspace::load(&path),
+        ),
+    };
+    if workspace.is_none() && opt.binary.is_none() {
+        eprintln!(
+            "Unable to read workspace from {:?}, only the lockfile is used",
+            path.join("Cargo.toml")
+        );
+    }
+
+    let vendor = open_vendor(&vendordir, opt.verify_files, opt.debug);
 
-    // Now output the values.
     if opt.debug {
-        for pkg in &config.package {
-            eprintln!("DEBUG -> pkg -> {:?}", pkg);
+        for pkg in &lock.packages {
+            eprintln!(
+                "DEBUG -> pkg -> {} {} ({})",
+                pkg.name,
+                pkg.version,
+                pkg.kind()
+            );
+            if let Some(src) = &pkg.source {
+                eprintln!("DEBUG ->   source -> {}", src);
+            }
+            if let Some(sum) = &pkg.checksum {
+                eprintln!("DEBUG ->   checksum -> {}", sum);
+            }
+            for dep in &pkg.dependencies {
+                match lock.resolve(dep) {
+                    Some(d) => eprintln!("DEBUG ->   dep -> {} {}", d.name, d.version),
+                    None => eprintln!("DEBUG ->   dep -> {:?} unresolved!", dep),
+                }
+            }
         }
     }
 
-    for pkg in &config.package {
-        // There are some versions that can be problematic due to multiple hyphens.
-        // This tries to account for that ...
-        // 
+    let report = Report::generate(&lock, workspace.as_ref(), vendor.as_ref(), &options);
 
-        let mut version = pkg.version.clone();
-
-        let mut hyphens: Vec<_> = pkg.version
-            .char_indices()
-            .rev()
-            .filter_map(|(i, c)| if c == '-' { Some(i) } else { None })
-            .collect();
+    if let Some(spec) = &opt.check {
+        for diag in &report.diagnostics {
+            eprintln!("{}", diag);
+        }
+        let license = report.license.as_ref().map(|l| l.to_string());
+        let up_to_date =
+            match specfile::check_file(spec, &report.spec_provides(), license.as_deref()) {
+                Ok(Some(diff)) => {
+                    print!("{}", diff);
+                    false
+                }
+                Ok(None) => true,
+                Err(e) => {
+                    eprintln!("Unable to read spec file {:?} - {}", spec, e);
+                    std::process::exit(1);
+                }
+            };
+        if !up_to_date {
+            eprintln!("{:?} is out of date with Cargo.lock", spec);
+        }
+        if report.failed() {
+            eprintln!("Validation failed");
+        }
+        if !up_to_date || report.failed() {
+            std::process::exit(1);
+        }
+        if opt.debug {
+            eprintln!("DEBUG -> {:?} is up to date", spec);
+        }
+        return;
+    }
 
+    if let Some(spec) = &opt.update_spec {
+        for diag in &report.diagnostics {
+            eprintln!("{}", diag);
+        }
+        if report.failed() {
+            eprintln!("Validation failed, not updating {:?}", spec);
+            std::process::exit(1);
+        }
+        let update_license = opt.update_license;
+        let license = report.license.as_ref().map(|l| l.to_string());
+        if update_license && license.is_none() {
+            eprintln!("WARNING no license was found, the License tag is not updated");
+        }
+        let license = license.as_deref().filter(|_| update_license);
+        if let Err(e) = specfile::update_file(spec, &report.spec_provides(), license) {
+            eprintln!("Unable to update spec file {:?} - {}", spec, e);
+            std::process::exit(1);
+        }
         if opt.debug {
-            eprintln!("hypens -> {:?}", hyphens);
+            eprintln!("DEBUG -> updated {:?}", spec);
         }
+        return;
+    }
+
+    // Now output the values.
+    match opt.format {
+        Format::Spec => {
+            for diag in &report.diagnostics {
+                eprintln!("{}", diag);
+            }
 
-        // Remove the last one (should be the first hypen, if present)
-        hyphens.pop();
+            for provide in report.spec_provides() {
+                println!("Provides: {}", provide);
+            }
 
-        for i in hyphens.iter() {
-            version.replace_range(*i..(i+1), "_");
+            match &report.license {
+                Some(license) => println!("License: {}", license),
+                None => println!("License: "),
+            }
         }
+        // The diagnostics are part of the document.
+        Format::Json => println!("{}", json::render(&lock, vendor.as_ref(), &report)),
+        _ => {
+            for diag in &report.diagnostics {
+                eprintln!("{}", diag);
+            }
 
-        println!("Provides: bundled(crate({})) = {}", pkg.name, version);
+            let bom = Sbom::new(&lock, workspace.as_ref(), &report, sbom::build_time());
+            let doc = match opt.format {
+                Format::Spdx => spdx_sbom::tag_value(&bom),
+                Format::SpdxJson => spdx_sbom::json(&bom),
+                Format::Spdx3Json => spdx_sbom::json3(&bom),
+                Format::CycloneDxJson => cyclonedx::json(&bom),
+                _ => cyclonedx::xml(&bom),
+            };
+            print!("{}", doc);
+        }
     }
 
-    let mut license = String::new();
-    for lic in licenses.iter() {
-        license.push_str(&lic);
-        license.push_str(" AND ");
+    if report.failed() {
+        eprintln!("Validation failed");
+        std::process::exit(1);
     }
-    println!("License: {}", license);
 
     if opt.debug {
         eprintln!("DEBUG -> Success! 🎉");
@@ -0,0 +1,288 @@
+use crate::vendor::Vendor;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// A vendored Cargo.toml.
+///
+/// Crates published to a registry have inherited fields written out, but
+/// crates vendored from a git workspace may still use `license.workspace = true`
+/// and friends, which need to be resolved from `[workspace.package]` of the
+/// workspace root that owns them.
+#[derive(Debug)]
+pub struct Manifest {
+    /// The directory of this Cargo.toml, relative to the vendor root.
+    pub dir: PathBuf,
+    /// The full path, for messages.
+    path: PathBuf,
+    value: toml::Value,
+    /// The owning workspace root directory and its manifest, if it is needed
+    /// and could be found.
+    workspace: Option<(PathBuf, toml::Value)>,
+}
+
+#[derive(Debug)]
+pub enum ManifestError {
+    Missing(PathBuf),
+    Invalid(PathBuf, toml::de::Error),
+    /// A field is inherited, but the workspace root couldn't be found.
+    NoWorkspace(PathBuf, String),
+    /// A field is inherited, but `[workspace.package]` of the workspace root
+    /// doesn't set it.
+    MissingInherited(PathBuf, String),
+}
+
+impl fmt::Display for ManifestError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ManifestError::Missing(p) => write!(f, "{:?} not found", p),
+            ManifestError::Invalid(p, e) => write!(f, "{:?} is invalid - {}", p, e),
+            ManifestError::NoWorkspace(p, field) => write!(
+                f,
+                "{:?} inherits {} from a workspace that isn't vendored",
+                p, field
+            ),
+            ManifestError::MissingInherited(p, field) => write!(
+                f,
+                "{:?} inherits {}, but workspace.package.{} is missing",
+                p, field, field
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ManifestError {}
+
+/// The kind of a dependency, ordered from the most to the least needed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum DepKind {
+    Normal,
+    Build,
+    Dev,
+}
+
+impl fmt::Display for DepKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DepKind::Normal => write!(f, "normal"),
+            DepKind::Build => write!(f, "build"),
+            DepKind::Dev => write!(f, "dev"),
+        }
+    }
+}
+
+impl std::str::FromStr for DepKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "normal" => Ok(DepKind::Normal),
+            "build" => Ok(DepKind::Build),
+            "dev" => Ok(DepKind::Dev),
+            _ => Err(format!(
+                "unknown dependency kind {:?}, expected normal, build or dev",
+                s
+            )),
+        }
+    }
+}
+
+/// A dependency declared in a Cargo.toml.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DepSpec {
+    /// The key it is declared with, which features refer to it by.
+    pub key: String,
+    /// The name of the package, which differs from the key if it is renamed.
+    pub name: String,
+    pub kind: DepKind,
+    /// The `[target.<platform>]` it is declared for, if any.
+    pub target: Option<String>,
+    /// Only used if a feature enables it.
+    pub optional: bool,
+    /// The features of the dependency to enable.
+    pub features: Vec<String>,
+    pub default_features: bool,
+}
+
+const DEP_TABLES: &[(&str, DepKind)] = &[
+    ("dependencies", DepKind::Normal),
+    ("build-dependencies", DepKind::Build),
+    ("build_dependencies", DepKind::Build),
+    ("dev-dependencies", DepKind::Dev),
+    ("dev_dependencies", DepKind::Dev),
+];
+
+fn dep_tables(value: &toml::Value, target: Option<&str>, out: &mut Vec<DepSpec>) {
+    for (table, kind) in DEP_TABLES {
+        let deps = match value.get(table).and_then(|t| t.as_table()) {
+            Some(deps) => deps,
+            None => continue,
+        };
+        for (key, dep) in deps {
+            let name = dep.get("package").and_then(|p| p.as_str()).unwrap_or(key);
+            let default_features = ["default-features", "default_features"]
+                .iter()
+                .find_map(|k| dep.get(k).and_then(|v| v.as_bool()))
+                .unwrap_or(true);
+            out.push(DepSpec {
+                key: key.clone(),
+                name: name.to_string(),
+                kind: *kind,
+                target: target.map(str::to_string),
+                optional: dep.get("optional").and_then(|v| v.as_bool()) == Some(true),
+                features: string_list(dep, "features"),
+                default_features,
+            });
+        }
+    }
+}
+
+fn string_list(value: &toml::Value, key: &str) -> Vec<String> {
+    value
+        .get(key)
+        .and_then(|v| v.as_array())
+        .map(|a| {
+            a.iter()
+                .filter_map(|v| v.as_str())
+                .map(str::to_string)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn is_inherited(v: &toml::Value) -> bool {
+    v.get("workspace").and_then(|w| w.as_bool()) == Some(true)
+}
+
+fn read(vendor: &Vendor, dir: &Path) -> Result<toml::Value, ManifestError> {
+    let path = dir.join("Cargo.toml");
+    let buffer = vendor
+        .read(&path)
+        .ok_or_else(|| ManifestError::Missing(vendor.display(&path)))?;
+    toml::from_slice(&buffer).map_err(|e| ManifestError::Invalid(vendor.display(&path), e))
+}
+
+/// Find the workspace root, either from an explicit `package.workspace`
+/// path or by walking up the tree to the first `[workspace]`.
+fn find_workspace(
+    vendor: &Vendor,
+    dir: &Path,
+    value: &toml::Value,
+) -> Option<(PathBuf, toml::Value)> {
+    if let Some(path) = value
+        .get("package")
+        .and_then(|p| p.get("workspace"))
+        .and_then(|w| w.as_str())
+    {
+        let root = dir.join(path);
+        return read(vendor, &root).ok().map(|v| (root, v));
+    }
+
+    dir.ancestors()
+        .skip(1)
+        .filter_map(|root| read(vendor, root).ok().map(|v| (root.to_path_buf(), v)))
+        .find(|(_, v)| v.get("workspace").is_some())
+}
+
+impl Manifest {
+    pub fn load(vendor: &Vendor, dir: &Path) -> Result<Self, ManifestError> {
+        let value = read(vendor, dir)?;
+
+        let inherits = value
+            .get("package")
+            .and_then(|p| p.as_table())
+            .is_some_and(|p| p.values().any(is_inherited));
+        let workspace = if inherits {
+            find_workspace(vendor, dir, &value)
+        } else {
+            None
+        };
+
+        Ok(Manifest {
+            dir: dir.to_path_buf(),
+            path: vendor.display(&dir.join("Cargo.toml")),
+            value,
+            workspace,
+        })
+    }
+
+    /// Look up a `[package]` field, resolving it from `[workspace.package]`
+    /// if it is inherited. Returns the value and the directory any relative
+    /// path in it is relative to.
+    fn package_field(&self, key: &str) -> Result<Option<(&toml::Value, &Path)>, ManifestError> {
+        let v = match self.value.get("package").and_then(|p| p.get(key)) {
+            Some(v) => v,
+            None => return Ok(None),
+        };
+        if !is_inherited(v) {
+            return Ok(Some((v, &self.dir)));
+        }
+        let (root, ws) = self
+            .workspace
+            .as_ref()
+            .ok_or_else(|| ManifestError::NoWorkspace(self.path.clone(), key.to_string()))?;
+        ws.get("workspace")
+            .and_then(|w| w.get("package"))
+            .and_then(|p| p.get(key))
+            .map(|v| Some((v, root.as_path())))
+            .ok_or_else(|| ManifestError::MissingInherited(self.path.clone(), key.to_string()))
+    }
+
+    fn package_str(&self, key: &str) -> Result<Option<String>, ManifestError> {
+        Ok(self
+            .package_field(key)?
+            .and_then(|(v, _)| v.as_str())
+            .map(str::to_string))
+    }
+
+    pub fn name(&self) -> Result<Option<String>, ManifestError> {
+        self.package_str("name")
+    }
+
+    pub fn version(&self) -> Result<Option<String>, ManifestError> {
+        self.package_str("version")
+    }
+
+    pub fn license(&self) -> Result<Option<String>, ManifestError> {
+        self.package_str("license")
+    }
+
+    /// The native library the crate links to, from `links`.
+    pub fn links(&self) -> Result<Option<String>, ManifestError> {
+        self.package_str("links")
+    }
+
+    /// The license file relative to the vendor root. An inherited
+    /// license-file is relative to the workspace root, not this crate.
+    pub fn license_file(&self) -> Result<Option<PathBuf>, ManifestError> {
+        Ok(self
+            .package_field("license-file")?
+            .and_then(|(v, dir)| v.as_str().map(|f| dir.join(f))))
+    }
+
+    /// Every dependency, including those in `[target.<platform>]` tables.
+    pub fn dependencies(&self) -> Vec<DepSpec> {
+        let mut deps = Vec::new();
+        dep_tables(&self.value, None, &mut deps);
+        if let Some(targets) = self.value.get("target").and_then(|t| t.as_table()) {
+            for (platform, value) in targets {
+                dep_tables(value, Some(platform), &mut deps);
+            }
+        }
+        deps
+    }
+
+    /// The `[features]` table, each feature with what it enables.
+    pub fn features(&self) -> BTreeMap<String, Vec<String>> {
+        self.value
+            .get("features")
+            .and_then(|f| f.as_table())
+            .map(|features| {
+                features
+                    .keys()
+                    .map(|name| (name.clone(), string_list(&self.value["features"], name)))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
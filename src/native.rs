@@ -0,0 +1,618 @@
+//! Detect C libraries bundled inside `-sys` and `-src` crates.
+//!
+//! Crates such as libgit2-sys or openssl-src carry the complete sources of a
+//! native library, which a distribution has to declare as
+//! `bundled(libgit2) = 1.7.1` and license separately from the crate itself.
+
+use crate::diagnostic::{Diagnostics, Level};
+use crate::license::LicenseExpr;
+use crate::manifest::Manifest;
+use crate::rpmver;
+use crate::vendor::Vendor;
+use crate::vendored::VendoredCrate;
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::path::Path;
+
+/// Where the version of a library is written in its sources.
+enum VersionIn {
+    /// A `#define` of a quoted string, such as `"1.7.1"`.
+    Define(&'static str),
+    /// A `#define` for each of the major, minor and patch numbers, or a
+    /// `NAME=value` line of each in an OpenSSL `VERSION.dat`.
+    Parts([&'static str; 3]),
+}
+
+/// When a crate builds the library it bundles, rather than linking to the
+/// system one.
+enum Builds {
+    /// Always, unless told otherwise by an environment variable.
+    Always,
+    /// Only with one of these features. Otherwise the system library is used,
+    /// and the bundled one only if the system one isn't found.
+    With(&'static [&'static str]),
+    /// Unless one of these features asks for the system library.
+    Unless(&'static [&'static str]),
+}
+
+struct Known {
+    /// The name used in `bundled(...)`.
+    library: &'static str,
+    crates: &'static [&'static str],
+    /// The `links` key of the crate's Cargo.toml.
+    links: &'static [&'static str],
+    /// Directories holding the sources, relative to the crate.
+    dirs: &'static [&'static str],
+    /// Files holding the version, relative to the crate.
+    headers: &'static [(&'static str, VersionIn)],
+    /// The license of each release series, from the version it applies to.
+    licenses: &'static [(&'static str, &'static str)],
+    /// How to build against the system library instead.
+    hint: Option<&'static str>,
+    builds: Builds,
+}
+
+const KNOWN: &[Known] = &[
+    Known {
+        library: "libgit2",
+        crates: &["libgit2-sys"],
+        links: &["git2"],
+        dirs: &["libgit2"],
+        headers: &[(
+            "libgit2/include/git2/version.h",
+            VersionIn::Define("LIBGIT2_VERSION"),
+        )],
+        licenses: &[("0", "GPL-2.0-only WITH GCC-exception-2.0")],
+        hint: Some("set LIBGIT2_NO_VENDOR=1"),
+        builds: Builds::With(&["vendored"]),
+    },
+    Known {
+        library: "openssl",
+        crates: &["openssl-src"],
+        links: &[],
+        dirs: &["openssl"],
+        headers: &[
+            (
+                "openssl/VERSION.dat",
+                VersionIn::Parts(["MAJOR", "MINOR", "PATCH"]),
+            ),
+            (
+                "openssl/include/openssl/opensslv.h",
+                VersionIn::Define("OPENSSL_VERSION_TEXT"),
+            ),
+        ],
+        licenses: &[("0", "OpenSSL"), ("3", "Apache-2.0")],
+        hint: Some("disable the vendored feature of openssl"),
+        builds: Builds::Always,
+    },
+    Known {
+        library: "zlib",
+        crates: &["libz-sys"],
+        links: &["z"],
+        dirs: &["src/zlib"],
+        headers: &[("src/zlib/zlib.h", VersionIn::Define("ZLIB_VERSION"))],
+        licenses: &[("0", "Zlib")],
+        hint: None,
+        builds: Builds::With(&["static"]),
+    },
+    Known {
+        library: "zlib-ng",
+        crates: &["libz-ng-sys"],
+        links: &["z-ng"],
+        dirs: &["src/zlib-ng"],
+        headers: &[(
+            "src/zlib-ng/zlib-ng.h.in",
+            VersionIn::Define("ZLIBNG_VERSION"),
+        )],
+        licenses: &[("0", "Zlib")],
+        hint: None,
+        builds: Builds::Always,
+    },
+    Known {
+        library: "zstd",
+        crates: &["zstd-sys"],
+        links: &["zstd"],
+        dirs: &["zstd/lib"],
+        headers: &[(
+            "zstd/lib/zstd.h",
+            VersionIn::Parts([
+                "ZSTD_VERSION_MAJOR",
+                "ZSTD_VERSION_MINOR",
+                "ZSTD_VERSION_RELEASE",
+            ]),
+        )],
+        licenses: &[("0", "BSD-3-Clause OR GPL-2.0-only")],
+        hint: Some("set ZSTD_SYS_USE_PKG_CONFIG=1"),
+        builds: Builds::Unless(&["pkg-config"]),
+    },
+    Known {
+        library: "sqlite",
+        crates: &["libsqlite3-sys"],
+        links: &["sqlite3"],
+        dirs: &["sqlite3"],
+        headers: &[("sqlite3/sqlite3.h", VersionIn::Define("SQLITE_VERSION"))],
+        licenses: &[("0", "blessing")],
+        hint: Some("disable the bundled feature of libsqlite3-sys"),
+        builds: Builds::With(&["bundled", "bundled-windows", "bundled-sqlcipher"]),
+    },
+    Known {
+        library: "liblzma",
+        crates: &["lzma-sys"],
+        links: &["lzma"],
+        dirs: &["xz-5.2", "xz"],
+        headers: &[
+            (
+                "xz-5.2/src/liblzma/api/lzma/version.h",
+                VersionIn::Parts([
+                    "LZMA_VERSION_MAJOR",
+                    "LZMA_VERSION_MINOR",
+                    "LZMA_VERSION_PATCH",
+                ]),
+            ),
+            (
+                "xz/src/liblzma/api/lzma/version.h",
+                VersionIn::Parts([
+                    "LZMA_VERSION_MAJOR",
+                    "LZMA_VERSION_MINOR",
+                    "LZMA_VERSION_PATCH",
+                ]),
+            ),
+        ],
+        licenses: &[("0", "LicenseRef-Public-Domain"), ("5.6", "0BSD")],
+        hint: None,
+        builds: Builds::With(&["static"]),
+    },
+    Known {
+        library: "bzip2",
+        crates: &["bzip2-sys"],
+        links: &["bzip2"],
+        dirs: &["bzip2-1.0.8"],
+        headers: &[(
+            "bzip2-1.0.8/bzlib_private.h",
+            VersionIn::Define("BZ_VERSION"),
+        )],
+        licenses: &[("0", "bzip2-1.0.6")],
+        hint: None,
+        builds: Builds::With(&["static"]),
+    },
+    Known {
+        library: "curl",
+        crates: &["curl-sys"],
+        links: &["curl"],
+        dirs: &["curl"],
+        headers: &[(
+            "curl/include/curl/curlver.h",
+            VersionIn::Define("LIBCURL_VERSION"),
+        )],
+        licenses: &[("0", "curl")],
+        hint: None,
+        builds: Builds::With(&["static-curl"]),
+    },
+    Known {
+        library: "libssh2",
+        crates: &["libssh2-sys"],
+        links: &["ssh2"],
+        dirs: &["libssh2"],
+        headers: &[(
+            "libssh2/include/libssh2.h",
+            VersionIn::Define("LIBSSH2_VERSION"),
+        )],
+        licenses: &[("0", "BSD-3-Clause")],
+        hint: Some("set LIBSSH2_SYS_USE_PKG_CONFIG=1"),
+        builds: Builds::Always,
+    },
+    Known {
+        library: "nghttp2",
+        crates: &["libnghttp2-sys"],
+        links: &["nghttp2"],
+        dirs: &["nghttp2"],
+        headers: &[],
+        licenses: &[("0", "MIT")],
+        hint: None,
+        builds: Builds::Always,
+    },
+    Known {
+        library: "lz4",
+        crates: &["lz4-sys"],
+        links: &["lz4"],
+        dirs: &["liblz4"],
+        headers: &[(
+            "liblz4/lib/lz4.h",
+            VersionIn::Parts([
+                "LZ4_VERSION_MAJOR",
+                "LZ4_VERSION_MINOR",
+                "LZ4_VERSION_RELEASE",
+            ]),
+        )],
+        licenses: &[("0", "BSD-2-Clause")],
+        hint: None,
+        builds: Builds::Always,
+    },
+    Known {
+        library: "oniguruma",
+        crates: &["onig_sys"],
+        links: &["onig"],
+        dirs: &["oniguruma"],
+        headers: &[(
+            "oniguruma/src/oniguruma.h",
+            VersionIn::Parts([
+                "ONIGURUMA_VERSION_MAJOR",
+                "ONIGURUMA_VERSION_MINOR",
+                "ONIGURUMA_VERSION_TEENY",
+            ]),
+        )],
+        licenses: &[("0", "BSD-2-Clause")],
+        hint: Some("set RUSTONIG_SYSTEM_LIBONIG=1"),
+        builds: Builds::Always,
+    },
+    Known {
+        library: "jemalloc",
+        crates: &["tikv-jemalloc-sys", "jemalloc-sys"],
+        links: &["jemalloc"],
+        dirs: &["jemalloc"],
+        headers: &[],
+        licenses: &[("0", "BSD-2-Clause")],
+        hint: Some("set JEMALLOC_OVERRIDE to the system libjemalloc"),
+        builds: Builds::Always,
+    },
+    Known {
+        library: "mimalloc",
+        crates: &["libmimalloc-sys"],
+        links: &["mimalloc"],
+        dirs: &["c_src/mimalloc"],
+        headers: &[],
+        licenses: &[("0", "MIT")],
+        hint: None,
+        builds: Builds::Always,
+    },
+];
+
+/// A native library bundled in a vendored crate.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BundledLibrary {
+    pub name: String,
+    /// The version, already converted to be valid in an RPM, if it could be
+    /// found.
+    pub version: Option<String>,
+    pub license: LicenseExpr,
+    /// The crate that bundles it.
+    pub crate_name: String,
+    pub crate_version: String,
+}
+
+impl fmt::Display for BundledLibrary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.version {
+            Some(version) => write!(f, "bundled({}) = {}", self.name, version),
+            None => write!(f, "bundled({})", self.name),
+        }
+    }
+}
+
+/// Is this one of the files a library version is read from? Used to keep
+/// them when reading a vendor tarball.
+pub fn is_version_file(path: &Path) -> bool {
+    KNOWN
+        .iter()
+        .flat_map(|k| k.headers)
+        .any(|(header, _)| path.ends_with(header))
+}
+
+/// Is this a `bundled(...)` provide of a known library, as written in a spec?
+pub fn is_library_provide(provide: &str) -> bool {
+    KNOWN.iter().any(|k| {
+        provide
+            .strip_prefix("bundled(")
+            .and_then(|p| p.strip_prefix(k.library))
+            .is_some_and(|p| p.starts_with(')'))
+    })
+}
+
+/// The value of `#define name value`, or `name=value`.
+fn define<'a>(text: &'a str, name: &str) -> Option<&'a str> {
+    text.lines().find_map(|line| {
+        let line = line.trim();
+        let rest = match line.strip_prefix('#') {
+            Some(rest) => rest.trim_start().strip_prefix("define")?.trim_start(),
+            None => line,
+        };
+        let rest = rest.strip_prefix(name)?;
+        let value = match rest.trim_start().strip_prefix('=') {
+            Some(value) => value,
+            None if rest.starts_with(char::is_whitespace) => rest,
+            None => return None,
+        };
+        Some(value.trim().trim_matches('"'))
+    })
+}
+
+/// The version number at the start of a version string, such as 1.0.8 from
+/// "1.0.8, 13-Jul-2019" or 1.1.1w from "OpenSSL 1.1.1w  11 Sep 2023".
+fn leading_version(s: &str) -> Option<String> {
+    let start = s.find(|c: char| c.is_ascii_digit())?;
+    let version: String = s[start..]
+        .chars()
+        .take_while(|c| c.is_ascii_alphanumeric() || *c == '.')
+        .collect();
+    Some(version.trim_end_matches('.').to_string())
+}
+
+fn header_version(text: &str, version_in: &VersionIn) -> Option<String> {
+    match version_in {
+        VersionIn::Define(name) => leading_version(define(text, name)?),
+        VersionIn::Parts(names) => {
+            let parts = names
+                .iter()
+                .map(|n| define(text, n).map(|v| v.trim_matches('"')))
+                .collect::<Option<Vec<_>>>()?;
+            if parts.iter().all(|p| p.chars().all(|c| c.is_ascii_digit())) {
+                Some(parts.join("."))
+            } else {
+                None
+            }
+        }
+    }
+}
+
+/// Many `-sys` crates record the version they bundle as build metadata, such
+/// as `0.16.1+1.7.1` or `2.0.9+zstd.1.5.5`.
+fn metadata_version(crate_version: &str) -> Option<String> {
+    let (_, metadata) = crate_version.split_once('+')?;
+    leading_version(metadata)
+}
+
+fn license_for(known: &Known, version: Option<&str>) -> &'static str {
+    let version = version.map(rpmver::from_semver);
+    known
+        .licenses
+        .iter()
+        .rev()
+        .find(|(since, _)| {
+            version
+                .as_deref()
+                .is_none_or(|v| rpmver::rpmvercmp(v, since) != Ordering::Less)
+        })
+        .or_else(|| known.licenses.last())
+        .map(|(_, license)| *license)
+        .unwrap_or("LicenseRef-Unknown")
+}
+
+/// Find the native libraries bundled in the vendored crates. `features` are
+/// those enabled on each crate, by name and version, if they were resolved.
+pub fn detect(
+    vendor: &Vendor,
+    crates: &[VendoredCrate],
+    features: Option<&BTreeMap<(String, String), BTreeSet<String>>>,
+    diags: &mut Diagnostics,
+) -> Vec<BundledLibrary> {
+    let mut found = Vec::new();
+    for krate in crates {
+        let links = Manifest::load(vendor, &krate.dir)
+            .ok()
+            .and_then(|m| m.links().ok().flatten());
+        let known = KNOWN.iter().find(|k| {
+            k.crates.contains(&krate.name.as_str())
+                || links.as_deref().is_some_and(|l| k.links.contains(&l))
+        });
+        let known = match known {
+            Some(known) => known,
+            None => continue,
+        };
+
+        let from_header = known.headers.iter().find_map(|(header, version_in)| {
+            let text = vendor.read(&krate.dir.join(header))?;
+            header_version(&String::from_utf8_lossy(&text), version_in)
+        });
+        let has_sources = from_header.is_some()
+            || known
+                .dirs
+                .iter()
+                .any(|d| !vendor.list(&krate.dir.join(d)).is_empty());
+        if !has_sources {
+            diags.add_for(
+                Level::Debug,
+                &krate.name,
+                &krate.version,
+                format!("does not bundle the sources of {}", known.library),
+            );
+            continue;
+        }
+
+        let version = from_header.or_else(|| metadata_version(&krate.version));
+        let described = format!(
+            "{}{}",
+            known.library,
+            version
+                .as_deref()
+                .map(|v| format!(" {}", v))
+                .unwrap_or_default()
+        );
+
+        let enabled = features.and_then(|f| f.get(&(krate.name.clone(), krate.version.clone())));
+        let any_enabled = |names: &[&str]| enabled.map(|e| names.iter().any(|n| e.contains(*n)));
+        let bundles = match known.builds {
+            Builds::Always => Some(true),
+            Builds::With(names) => any_enabled(names),
+            // Without the features we go by the default, which bundles.
+            Builds::Unless(names) => Some(!any_enabled(names).unwrap_or(false)),
+        };
+        match (bundles, &known.builds) {
+            (Some(true), _) => {}
+            (Some(false), Builds::With(_)) => {
+                diags.add_for(
+                    Level::Warning,
+                    &krate.name,
+                    &krate.version,
+                    format!(
+                        "builds the bundled {} if the system library isn't found, make sure \
+                         it is installed",
+                        described
+                    ),
+                );
+                continue;
+            }
+            (Some(false), _) => {
+                diags.add_for(
+                    Level::Debug,
+                    &krate.name,
+                    &krate.version,
+                    format!("uses the system {}", known.library),
+                );
+                continue;
+            }
+            (None, _) => {
+                diags.add_for(
+                    Level::Warning,
+                    &krate.name,
+                    &krate.version,
+                    format!(
+                        "may bundle {}, depending on its features and whether the system \
+                         library is found, use --feature-filter to find out",
+                        described
+                    ),
+                );
+                continue;
+            }
+        }
+
+        let license = license_for(known, version.as_deref());
+        let license = match LicenseExpr::parse(license) {
+            Ok(license) => license,
+            Err(_) => continue,
+        };
+
+        let mut message = format!(
+            "bundles {}, consider building against the system library instead",
+            described
+        );
+        if let Some(hint) = known.hint {
+            message.push_str(&format!(" ({})", hint));
+        }
+        diags.add_for(Level::Warning, &krate.name, &krate.version, message);
+        if version.is_none() {
+            diags.add_for(
+                Level::Warning,
+                &krate.name,
+                &krate.version,
+                format!(
+                    "unable to find the version of the bundled {}",
+                    known.library
+                ),
+            );
+        }
+
+        found.push(BundledLibrary {
+            name: known.library.to_string(),
+            version: version.map(|v| rpmver::from_semver(&v)),
+            license,
+            crate_name: krate.name.clone(),
+            crate_version: krate.version.clone(),
+        });
+    }
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    const ZLIB_H: &str = "#define ZLIB_VERSION \"1.3.1\"\n";
+
+    fn libz_sys() -> (Vendor, Vec<VendoredCrate>) {
+        let vendor = Vendor::from_files(&[
+            (
+                "libz-sys/Cargo.toml",
+                "[package]\nname = \"libz-sys\"\nversion = \"1.1.20\"\nlinks = \"z\"\n",
+            ),
+            ("libz-sys/src/zlib/zlib.h", ZLIB_H),
+        ]);
+        let krate = VendoredCrate {
+            name: "libz-sys".to_string(),
+            version: "1.1.20".to_string(),
+            dir: PathBuf::from("libz-sys"),
+            license: None,
+            license_source: crate::vendored::LicenseSource::Declared,
+        };
+        (vendor, vec![krate])
+    }
+
+    fn enabled(features: &[&str]) -> BTreeMap<(String, String), BTreeSet<String>> {
+        let mut map = BTreeMap::new();
+        map.insert(
+            ("libz-sys".to_string(), "1.1.20".to_string()),
+            features.iter().map(|f| f.to_string()).collect(),
+        );
+        map
+    }
+
+    fn warnings(diags: Diagnostics) -> Vec<String> {
+        diags
+            .into_vec()
+            .into_iter()
+            .filter(|d| d.level == Level::Warning)
+            .map(|d| d.message)
+            .collect()
+    }
+
+    #[test]
+    fn sources_alone_are_only_a_hint() {
+        let (vendor, crates) = libz_sys();
+        let mut diags = Diagnostics::new(false);
+        assert!(detect(&vendor, &crates, None, &mut diags).is_empty());
+        let warnings = warnings(diags);
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].starts_with("may bundle zlib 1.3.1"));
+    }
+
+    #[test]
+    fn system_library_without_the_feature() {
+        let (vendor, crates) = libz_sys();
+        let mut diags = Diagnostics::new(false);
+        let features = enabled(&["default", "libc"]);
+        assert!(detect(&vendor, &crates, Some(&features), &mut diags).is_empty());
+        let warnings = warnings(diags);
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].starts_with("builds the bundled zlib 1.3.1 if"));
+    }
+
+    #[test]
+    fn bundled_with_the_feature() {
+        let (vendor, crates) = libz_sys();
+        let mut diags = Diagnostics::new(false);
+        let features = enabled(&["default", "static"]);
+        let found = detect(&vendor, &crates, Some(&features), &mut diags);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].name, "zlib");
+        assert_eq!(found[0].version.as_deref(), Some("1.3.1"));
+        assert_eq!(found[0].license.to_string(), "Zlib");
+    }
+
+    #[test]
+    fn unless_bundles_by_default() {
+        let vendor = Vendor::from_files(&[(
+            "zstd-sys/zstd/lib/zstd.h",
+            "#define ZSTD_VERSION_MAJOR 1\n#define ZSTD_VERSION_MINOR 5\n\
+             #define ZSTD_VERSION_RELEASE 6\n",
+        )]);
+        let crates = vec![VendoredCrate {
+            name: "zstd-sys".to_string(),
+            version: "2.0.13+zstd.1.5.6".to_string(),
+            dir: PathBuf::from("zstd-sys"),
+            license: None,
+            license_source: crate::vendored::LicenseSource::Declared,
+        }];
+        let mut diags = Diagnostics::new(false);
+        assert_eq!(detect(&vendor, &crates, None, &mut diags).len(), 1);
+
+        let mut features = BTreeMap::new();
+        features.insert(
+            ("zstd-sys".to_string(), "2.0.13+zstd.1.5.6".to_string()),
+            vec!["pkg-config".to_string()].into_iter().collect(),
+        );
+        let mut diags = Diagnostics::new(false);
+        assert!(detect(&vendor, &crates, Some(&features), &mut diags).is_empty());
+        assert!(warnings(diags).is_empty());
+    }
+}
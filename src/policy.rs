@@ -0,0 +1,266 @@
+use crate::license::LicenseExpr;
+use crate::spdx;
+use std::collections::BTreeSet;
+use std::fmt;
+use std::io;
+use std::path::Path;
+
+/// A problem with a single license in a crate's license expression.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Problem {
+    /// Not on the SPDX license list, and not a LicenseRef.
+    Invalid(String),
+    InvalidException(String),
+    Deprecated {
+        id: String,
+        replacement: Option<String>,
+    },
+    /// An allow list is in use and this license isn't on it.
+    NotAllowed(String),
+    Denied(String),
+    /// The crate's license couldn't be determined, so it can't be checked.
+    Unknown,
+}
+
+impl Problem {
+    /// Deprecated ids are still valid, so they are only a warning, as is an
+    /// unknown license unless `deny_unknown`. Anything else fails validation.
+    pub fn is_error(&self, deny_unknown: bool) -> bool {
+        match self {
+            Problem::Deprecated { .. } => false,
+            Problem::Unknown => deny_unknown,
+            _ => true,
+        }
+    }
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Problem::Invalid(id) => write!(f, "{} is not a valid SPDX license identifier", id),
+            Problem::InvalidException(id) => {
+                write!(f, "{} is not a valid SPDX license exception", id)
+            }
+            Problem::Deprecated {
+                id,
+                replacement: Some(r),
+            } => write!(f, "{} is deprecated, use {}", id, r),
+            Problem::Deprecated {
+                id,
+                replacement: None,
+            } => write!(f, "{} is deprecated", id),
+            Problem::NotAllowed(id) => write!(f, "{} is not on the allow list", id),
+            Problem::Denied(id) => write!(f, "{} is on the deny list", id),
+            Problem::Unknown => write!(f, "the license is unknown, so it can't be validated"),
+        }
+    }
+}
+
+/// Distribution license policy.
+///
+/// The lists are plain text files with one license or `license WITH exception`
+/// per line, and `#` comments, so Fedora's "allowed" and "not-allowed" lists
+/// or openSUSE's accepted licenses can be exported into them.
+#[derive(Debug, Default)]
+pub struct Policy {
+    allow: Option<BTreeSet<String>>,
+    deny: BTreeSet<String>,
+}
+
+fn read_list(path: &Path) -> io::Result<BTreeSet<String>> {
+    Ok(std::fs::read_to_string(path)?
+        .lines()
+        .map(|l| l.split('#').next().unwrap_or("").trim())
+        .filter(|l| !l.is_empty())
+        .map(str::to_lowercase)
+        .collect())
+}
+
+impl Policy {
+    pub fn load(allow: Option<&Path>, deny: Option<&Path>) -> io::Result<Self> {
+        Ok(Policy {
+            allow: allow.map(read_list).transpose()?,
+            deny: deny.map(read_list).transpose()?.unwrap_or_default(),
+        })
+    }
+
+    /// Is there an allow or deny list to enforce, rather than only the SPDX
+    /// license list?
+    pub fn has_lists(&self) -> bool {
+        self.allow.is_some() || !self.deny.is_empty()
+    }
+
+    /// A single license term is matched by its full text, such as
+    /// "Apache-2.0 WITH LLVM-exception", or by its bare id.
+    fn listed(list: &BTreeSet<String>, term: &LicenseExpr, id: &str) -> bool {
+        list.contains(&term.to_string().to_lowercase()) || list.contains(&id.to_lowercase())
+    }
+
+    /// Check an expression against the policy. An OR is acceptable if any
+    /// of its choices are, an AND only if all of its terms are.
+    fn accept(&self, expr: &LicenseExpr, problems: &mut Vec<Problem>) -> bool {
+        match expr {
+            LicenseExpr::License { id, .. } => {
+                if Self::listed(&self.deny, expr, id) {
+                    problems.push(Problem::Denied(expr.to_string()));
+                    false
+                } else if let Some(allow) = &self.allow {
+                    if Self::listed(allow, expr, id) {
+                        true
+                    } else {
+                        problems.push(Problem::NotAllowed(expr.to_string()));
+                        false
+                    }
+                } else {
+                    true
+                }
+            }
+            LicenseExpr::And(terms) => {
+                let mut ok = true;
+                for t in terms {
+                    ok &= self.accept(t, problems);
+                }
+                ok
+            }
+            LicenseExpr::Or(terms) => {
+                let mut rejected = Vec::new();
+                // Check every choice, so all rejections are reported.
+                let accepted: Vec<bool> = terms
+                    .iter()
+                    .map(|t| self.accept(t, &mut rejected))
+                    .collect();
+                let ok = accepted.contains(&true);
+                // Rejected alternatives don't matter if one choice is fine.
+                if !ok {
+                    problems.extend(rejected);
+                }
+                ok
+            }
+        }
+    }
+
+    /// Validate an expression against the SPDX license list and this policy.
+    /// A missing license is a problem of its own.
+    pub fn check(&self, expr: Option<&LicenseExpr>) -> Vec<Problem> {
+        let expr = match expr {
+            Some(expr) => expr,
+            None => return vec![Problem::Unknown],
+        };
+        let mut problems = Vec::new();
+        validate_ids(expr, &mut problems);
+        self.accept(expr, &mut problems);
+        problems
+    }
+}
+
+fn validate_ids(expr: &LicenseExpr, problems: &mut Vec<Problem>) {
+    match expr {
+        LicenseExpr::License {
+            id,
+            plus,
+            exception,
+        } => {
+            if !spdx::is_license_ref(id) {
+                // The list has GPL-2.0+ and friends as their own deprecated ids.
+                let with_plus = format!("{}+", id);
+                let found = if *plus {
+                    spdx::license(&with_plus).or_else(|| spdx::license(id))
+                } else {
+                    spdx::license(id)
+                };
+                match found {
+                    None => problems.push(Problem::Invalid(id.clone())),
+                    Some((_, true)) => problems.push(Problem::Deprecated {
+                        id: if *plus { with_plus } else { id.clone() },
+                        replacement: spdx::replacement(id, *plus).map(str::to_string),
+                    }),
+                    Some((_, false)) => {}
+                }
+            }
+            if let Some(e) = exception {
+                match spdx::exception(e) {
+                    None => problems.push(Problem::InvalidException(e.clone())),
+                    Some((_, true)) => problems.push(Problem::Deprecated {
+                        id: e.clone(),
+                        replacement: None,
+                    }),
+                    Some((_, false)) => {}
+                }
+            }
+        }
+        LicenseExpr::And(terms) | LicenseExpr::Or(terms) => {
+            for t in terms {
+                validate_ids(t, problems);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(policy: &Policy, license: &str) -> Vec<Problem> {
+        policy.check(Some(&LicenseExpr::parse(license).unwrap()))
+    }
+
+    #[test]
+    fn unknown_licenses_are_reported() {
+        let problems = Policy::default().check(None);
+        assert_eq!(problems, [Problem::Unknown]);
+        assert!(!problems[0].is_error(false));
+        assert!(problems[0].is_error(true));
+    }
+
+    #[test]
+    fn has_lists() {
+        assert!(!Policy::default().has_lists());
+        let policy = Policy {
+            deny: ["gpl-3.0-only".to_string()].iter().cloned().collect(),
+            ..Policy::default()
+        };
+        assert!(policy.has_lists());
+    }
+
+    #[test]
+    fn spdx_ids() {
+        let policy = Policy::default();
+        assert!(check(&policy, "MIT OR Apache-2.0").is_empty());
+        assert!(check(&policy, "LicenseRef-Custom").is_empty());
+        assert_eq!(
+            check(&policy, "MIT AND Not-A-License"),
+            [Problem::Invalid("Not-A-License".to_string())]
+        );
+        let deprecated = check(&policy, "GPL-2.0+");
+        assert!(matches!(
+            deprecated.as_slice(),
+            [Problem::Deprecated { .. }]
+        ));
+        assert!(!deprecated[0].is_error(true));
+    }
+
+    #[test]
+    fn lists() {
+        let policy = Policy {
+            allow: Some(
+                ["mit", "apache-2.0"]
+                    .iter()
+                    .map(|s| s.to_string())
+                    .collect(),
+            ),
+            deny: ["gpl-3.0-only"].iter().map(|s| s.to_string()).collect(),
+        };
+        assert!(check(&policy, "MIT OR GPL-3.0-only").is_empty());
+        assert_eq!(
+            check(&policy, "MIT AND Zlib"),
+            [Problem::NotAllowed("Zlib".to_string())]
+        );
+        assert_eq!(
+            check(&policy, "GPL-3.0-only OR Zlib"),
+            [
+                Problem::Denied("GPL-3.0-only".to_string()),
+                Problem::NotAllowed("Zlib".to_string())
+            ]
+        );
+    }
+}
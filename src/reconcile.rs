@@ -0,0 +1,252 @@
+//! Find the differences between the crates in a lockfile and the crates in
+//! its vendor tree.
+
+use crate::lockfile::LockedPackage;
+use crate::manifest::Manifest;
+use crate::vendor::Vendor;
+use std::collections::BTreeSet;
+use std::fmt;
+use std::path::PathBuf;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Discrepancy {
+    /// A vendored crate that isn't in the lockfile, usually left over from
+    /// an earlier `cargo vendor`.
+    Orphaned {
+        dir: PathBuf,
+        name: String,
+        version: Option<String>,
+    },
+    /// A locked crate that isn't vendored at all.
+    Missing { name: String, version: String },
+    /// The crate is vendored, but not the locked version.
+    VersionMismatch {
+        name: String,
+        locked: String,
+        vendored: String,
+        dir: PathBuf,
+    },
+}
+
+impl fmt::Display for Discrepancy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Discrepancy::Orphaned {
+                dir,
+                name,
+                version: Some(version),
+            } => write!(
+                f,
+                "{:?} contains {} {}, which is not in the lockfile",
+                dir, name, version
+            ),
+            Discrepancy::Orphaned { dir, name, .. } => {
+                write!(
+                    f,
+                    "{:?} contains {}, which is not in the lockfile",
+                    dir, name
+                )
+            }
+            Discrepancy::Missing { name, version } => {
+                write!(f, "{} {} is locked, but not vendored", name, version)
+            }
+            Discrepancy::VersionMismatch {
+                name,
+                locked,
+                vendored,
+                dir,
+            } => write!(
+                f,
+                "{} {} is locked, but {:?} contains version {}",
+                name, locked, dir, vendored
+            ),
+        }
+    }
+}
+
+/// Compare the locked packages that should be vendored with the crates in
+/// the vendor tree.
+pub fn reconcile(vendor: &Vendor, packages: &[&LockedPackage]) -> Vec<Discrepancy> {
+    // The name and version each vendored crate claims to be.
+    let vendored: Vec<(PathBuf, String, Option<String>)> = vendor
+        .crate_dirs()
+        .into_iter()
+        .map(|dir| {
+            let manifest = Manifest::load(vendor, &dir).ok();
+            let name = manifest
+                .as_ref()
+                .and_then(|m| m.name().ok().flatten())
+                .unwrap_or_else(|| dir.to_string_lossy().into_owned());
+            let version = manifest.and_then(|m| m.version().ok().flatten());
+            (dir, name, version)
+        })
+        .collect();
+
+    let mut claimed = BTreeSet::new();
+    let mut unlocated = Vec::new();
+    for pkg in packages {
+        match vendor.locate(&pkg.name, &pkg.version) {
+            Some(dir) => {
+                claimed.insert(dir);
+            }
+            None => unlocated.push(pkg),
+        }
+    }
+
+    let mut found = Vec::new();
+    for pkg in unlocated {
+        let other = vendored
+            .iter()
+            .find(|(dir, name, _)| name == &pkg.name && !claimed.contains(dir));
+        match other {
+            Some((dir, _, version)) => {
+                claimed.insert(dir.clone());
+                found.push(Discrepancy::VersionMismatch {
+                    name: pkg.name.clone(),
+                    locked: pkg.version.clone(),
+                    vendored: version.clone().unwrap_or_else(|| "unknown".to_string()),
+                    dir: vendor.display(dir),
+                });
+            }
+            None => found.push(Discrepancy::Missing {
+                name: pkg.name.clone(),
+                version: pkg.version.clone(),
+            }),
+        }
+    }
+
+    for (dir, name, version) in vendored {
+        if !claimed.contains(&dir) {
+            found.push(Discrepancy::Orphaned {
+                dir: vendor.display(&dir),
+                name,
+                version,
+            });
+        }
+    }
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lockfile::LockFile;
+
+    fn manifest(name: &str, version: &str) -> String {
+        format!(
+            "[package]\nname = \"{}\"\nversion = \"{}\"\n",
+            name, version
+        )
+    }
+
+    fn lock(packages: &[(&str, &str)]) -> LockFile {
+        let mut text = String::from("version = 4\n");
+        for (name, version) in packages {
+            text.push_str(&format!(
+                "\n[[package]]\nname = \"{}\"\nversion = \"{}\"\n\
+                 source = \"registry+https://github.com/rust-lang/crates.io-index\"\n",
+                name, version
+            ));
+        }
+        LockFile::parse(text.as_bytes()).unwrap()
+    }
+
+    fn run(lock: &LockFile, files: &[(&str, String)]) -> Vec<Discrepancy> {
+        let files: Vec<(&str, &str)> = files.iter().map(|(p, t)| (*p, t.as_str())).collect();
+        let vendor = Vendor::from_files(&files);
+        let packages: Vec<&LockedPackage> = lock.packages.iter().collect();
+        reconcile(&vendor, &packages)
+    }
+
+    fn dir(name: &str) -> PathBuf {
+        PathBuf::from(format!("vendor.tar:{}", name))
+    }
+
+    #[test]
+    fn matching_trees() {
+        let lock = lock(&[("itoa", "1.0.11"), ("syn", "1.0.109"), ("syn", "2.0.60")]);
+        // A plain tree falls back to versioned directories for duplicates.
+        let plain = [
+            ("itoa/Cargo.toml", manifest("itoa", "1.0.11")),
+            ("syn/Cargo.toml", manifest("syn", "2.0.60")),
+            ("syn-1.0.109/Cargo.toml", manifest("syn", "1.0.109")),
+        ];
+        assert!(run(&lock, &plain).is_empty());
+
+        let versioned = [
+            ("itoa-1.0.11/Cargo.toml", manifest("itoa", "1.0.11")),
+            ("syn-1.0.109/Cargo.toml", manifest("syn", "1.0.109")),
+            ("syn-2.0.60/Cargo.toml", manifest("syn", "2.0.60")),
+        ];
+        assert!(run(&lock, &versioned).is_empty());
+    }
+
+    #[test]
+    fn orphaned_and_missing() {
+        let lock = lock(&[("itoa", "1.0.11"), ("ryu", "1.0.18")]);
+        let files = [
+            ("itoa/Cargo.toml", manifest("itoa", "1.0.11")),
+            ("old-1.0.0/Cargo.toml", manifest("old", "1.0.0")),
+        ];
+        assert_eq!(
+            run(&lock, &files),
+            [
+                Discrepancy::Missing {
+                    name: "ryu".to_string(),
+                    version: "1.0.18".to_string(),
+                },
+                Discrepancy::Orphaned {
+                    dir: dir("old-1.0.0"),
+                    name: "old".to_string(),
+                    version: Some("1.0.0".to_string()),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn version_mismatches() {
+        let lock = lock(&[("itoa", "1.0.11"), ("syn", "2.0.60")]);
+        // Plain directories, and --versioned-dirs left over from an older
+        // lockfile.
+        let files = [
+            ("itoa/Cargo.toml", manifest("itoa", "1.0.10")),
+            ("syn-2.0.50/Cargo.toml", manifest("syn", "2.0.50")),
+        ];
+        assert_eq!(
+            run(&lock, &files),
+            [
+                Discrepancy::VersionMismatch {
+                    name: "itoa".to_string(),
+                    locked: "1.0.11".to_string(),
+                    vendored: "1.0.10".to_string(),
+                    dir: dir("itoa"),
+                },
+                Discrepancy::VersionMismatch {
+                    name: "syn".to_string(),
+                    locked: "2.0.60".to_string(),
+                    vendored: "2.0.50".to_string(),
+                    dir: dir("syn-2.0.50"),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn each_directory_explains_one_package() {
+        // Two locked versions and one stale directory: the other version is
+        // missing, not mismatched with the same directory twice.
+        let lock = lock(&[("syn", "1.0.109"), ("syn", "2.0.60")]);
+        let files = [("syn-2.0.50/Cargo.toml", manifest("syn", "2.0.50"))];
+        let found = run(&lock, &files);
+        assert_eq!(found.len(), 2);
+        assert!(matches!(found[0], Discrepancy::VersionMismatch { .. }));
+        assert_eq!(
+            found[1],
+            Discrepancy::Missing {
+                name: "syn".to_string(),
+                version: "2.0.60".to_string(),
+            }
+        );
+    }
+}
@@ -0,0 +1,564 @@
+use crate::diagnostic::{Diagnostic, Diagnostics, Level};
+use crate::graph::{self, Features};
+use crate::license::LicenseExpr;
+use crate::lockfile::{LockFile, LockedPackage, SourceKind};
+use crate::manifest::DepKind;
+use crate::native::{self, BundledLibrary};
+use crate::policy::Policy;
+use crate::reconcile;
+use crate::rpmver;
+use crate::spdx;
+use crate::target::Target;
+use crate::vendor::Vendor;
+use crate::vendored::VendoredCrate;
+use crate::verify;
+use crate::workspace::Workspace;
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// How to generate a report.
+#[derive(Debug, Default)]
+pub struct Options {
+    pub debug: bool,
+    /// Include workspace members and path dependencies, which are not bundled.
+    pub include_local: bool,
+    /// Simplify the combined license expression.
+    pub simplify: bool,
+    /// Licenses to choose from each OR, in order of preference. Implies simplify.
+    pub prefer: Vec<String>,
+    /// Validate every crate's license against this policy.
+    pub policy: Option<Policy>,
+    /// Check that the vendored crates match the checksums in the lockfile.
+    pub verify: bool,
+    /// Also check every file of the vendored crates. Implies verify.
+    pub verify_files: bool,
+    /// Report crates that are locked but not vendored, or vendored but not
+    /// locked.
+    pub reconcile: bool,
+    /// Fail on any difference between the lockfile and vendor tree. Implies
+    /// reconcile.
+    pub strict: bool,
+    /// Fail validation when a crate's license is unknown.
+    pub deny_unknown_licenses: bool,
+    /// Only include the crates that are built for this target.
+    pub target: Option<Target>,
+    /// Only include the crates used by these features of the workspace.
+    pub features: Option<Features>,
+    /// The kinds of dependency to list in the provides, or all if empty.
+    pub provide_kinds: Vec<DepKind>,
+    /// The kinds of dependency whose licenses make up the License tag, or
+    /// all if empty.
+    pub license_kinds: Vec<DepKind>,
+}
+
+/// A single `bundled(crate(name)) = version` provide.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Provide {
+    pub name: String,
+    /// The version, already converted to be valid in an RPM.
+    pub version: String,
+}
+
+impl fmt::Display for Provide {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "bundled(crate({})) = {}", self.name, self.version)
+    }
+}
+
+/// Everything we learnt about a lockfile and its vendor tree.
+#[derive(Debug)]
+pub struct Report {
+    pub provides: Vec<Provide>,
+    /// The bundled crates that were found in the vendor tree.
+    pub crates: Vec<VendoredCrate>,
+    /// The combined license of all bundled crates.
+    pub license: Option<LicenseExpr>,
+    /// The native libraries bundled in the crates, which are provided and
+    /// licensed too.
+    pub libraries: Vec<BundledLibrary>,
+    /// How each bundled package is needed, keyed by name and version. Only
+    /// known when the dependency graph was walked.
+    pub kinds: BTreeMap<(String, String), DepKind>,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl Report {
+    pub fn generate(
+        lock: &LockFile,
+        workspace: Option<&Workspace>,
+        vendor: Option<&Vendor>,
+        options: &Options,
+    ) -> Self {
+        let mut diags = Diagnostics::new(options.debug);
+
+        if let Some(ws) = workspace {
+            for member in &ws.members {
+                diags.add(
+                    Level::Debug,
+                    format!("workspace member {} at {:?}", member.name, member.path),
+                );
+                if !lock
+                    .packages
+                    .iter()
+                    .any(|pkg| pkg.name == member.name && pkg.kind() == SourceKind::Local)
+                {
+                    diags.add(
+                        Level::Warning,
+                        format!(
+                            "workspace member {} is not in the lockfile, it may be out of date",
+                            member.name
+                        ),
+                    );
+                }
+            }
+        }
+
+        // Workspace members and path dependencies are built from this source
+        // tree, so they aren't bundled unless asked for.
+        let mut bundled: Vec<&LockedPackage> = lock
+            .packages
+            .iter()
+            .filter(|pkg| {
+                let kind = pkg.kind();
+                if kind == SourceKind::Local {
+                    let is_member = workspace.is_some_and(|ws| ws.is_member(&pkg.name));
+                    diags.add(
+                        Level::Debug,
+                        format!(
+                            "{} is a {}",
+                            pkg.name,
+                            if is_member {
+                                "workspace member"
+                            } else {
+                                "path dependency"
+                            }
+                        ),
+                    );
+                }
+                options.include_local || kind != SourceKind::Local
+            })
+            .collect();
+
+        // Every third party package the vendor tree should hold, whether or
+        // not it is built for the target or features asked for.
+        let vendored: Vec<&LockedPackage> = lock
+            .packages
+            .iter()
+            .filter(|pkg| pkg.kind() != SourceKind::Local)
+            .collect();
+
+        let classify = options.target.is_some()
+            || options.features.is_some()
+            || !options.provide_kinds.is_empty()
+            || !options.license_kinds.is_empty();
+        let mut kinds = BTreeMap::new();
+        // The features of each package, if they were resolved.
+        let mut features = None;
+        if classify {
+            let classes = graph::classify(
+                lock,
+                workspace,
+                vendor,
+                options.target.as_ref(),
+                options.features.as_ref(),
+                &mut diags,
+            );
+            bundled.retain(|pkg| {
+                let kind = lock
+                    .packages
+                    .iter()
+                    .zip(&classes.kinds)
+                    .find(|(p, _)| std::ptr::eq(*p, *pkg))
+                    .and_then(|(_, &kind)| kind);
+                match kind {
+                    Some(kind) => {
+                        if kind != DepKind::Normal {
+                            diags.add_for(
+                                Level::Debug,
+                                &pkg.name,
+                                &pkg.version,
+                                format!("is only a {} dependency", kind),
+                            );
+                        }
+                        kinds.insert((pkg.name.clone(), pkg.version.clone()), kind);
+                        true
+                    }
+                    None => {
+                        let mut why = "is not needed by the workspace".to_string();
+                        if let Some(target) = &options.target {
+                            why.push_str(&format!(" on {}", target.triple));
+                        }
+                        if options.features.is_some() {
+                            why.push_str(" with the enabled features");
+                        }
+                        diags.add_for(Level::Debug, &pkg.name, &pkg.version, why);
+                        false
+                    }
+                }
+            });
+            if options.features.is_some() {
+                features = Some(
+                    lock.packages
+                        .iter()
+                        .zip(classes.features)
+                        .filter_map(|(pkg, f)| Some(((pkg.name.clone(), pkg.version.clone()), f?)))
+                        .collect::<BTreeMap<_, _>>(),
+                );
+            }
+        }
+        // Whether a package's kind is one of those asked for.
+        let wanted = |pkg_kinds: &[DepKind], name: &str, version: &str| {
+            pkg_kinds.is_empty()
+                || kinds
+                    .get(&(name.to_string(), version.to_string()))
+                    .is_none_or(|k| pkg_kinds.contains(k))
+        };
+
+        // Local packages, included with include_local, aren't in the vendor
+        // tree.
+        let crates: Vec<VendoredCrate> = match vendor {
+            Some(vendor) => bundled
+                .iter()
+                .filter(|pkg| pkg.kind() != SourceKind::Local)
+                .filter_map(|pkg| VendoredCrate::load(vendor, &pkg.name, &pkg.version, &mut diags))
+                .collect(),
+            None => Vec::new(),
+        };
+
+        if let (Some(vendor), true) = (vendor, options.reconcile || options.strict) {
+            let level = if options.strict {
+                Level::Error
+            } else {
+                Level::Warning
+            };
+            for discrepancy in reconcile::reconcile(vendor, &vendored) {
+                diags.add(level, discrepancy.to_string());
+            }
+        }
+
+        let verify = options.verify || options.verify_files;
+        if verify && vendor.is_none() {
+            diags.add(Level::Error, "there is no vendor tree to verify");
+        }
+        if let (Some(vendor), true) = (vendor, verify) {
+            for pkg in &vendored {
+                let dir = match vendor.locate(&pkg.name, &pkg.version) {
+                    Some(dir) => dir,
+                    None => {
+                        diags.add_for(
+                            Level::Error,
+                            &pkg.name,
+                            &pkg.version,
+                            "is locked, but missing from the vendor tree",
+                        );
+                        continue;
+                    }
+                };
+                for problem in verify::verify(vendor, pkg, &dir, options.verify_files) {
+                    diags.add_for(Level::Error, &pkg.name, &pkg.version, problem.to_string());
+                }
+            }
+        }
+
+        let mut libraries = match vendor {
+            Some(vendor) => native::detect(vendor, &crates, features.as_ref(), &mut diags),
+            None => Vec::new(),
+        };
+
+        if let Some(policy) = &options.policy {
+            diags.add(
+                Level::Debug,
+                format!(
+                    "validating against SPDX license list {}",
+                    spdx::LICENSE_LIST_VERSION
+                ),
+            );
+            if vendor.is_none() {
+                let level = if policy.has_lists() || options.deny_unknown_licenses {
+                    Level::Error
+                } else {
+                    Level::Warning
+                };
+                diags.add(
+                    level,
+                    "there is no vendor tree to find the licenses to validate",
+                );
+            }
+            // A package missing from the vendor tree has an unknown license,
+            // which must not pass unnoticed. Local packages aren't bundled
+            // code, so the policy doesn't apply to them.
+            for pkg in bundled
+                .iter()
+                .filter(|pkg| vendor.is_some() && pkg.kind() != SourceKind::Local)
+            {
+                let license = crates
+                    .iter()
+                    .find(|c| c.name == pkg.name && c.version == pkg.version)
+                    .and_then(|c| c.license.as_ref());
+                for problem in policy.check(license) {
+                    let level = if problem.is_error(options.deny_unknown_licenses) {
+                        Level::Error
+                    } else {
+                        Level::Warning
+                    };
+                    diags.add_for(level, &pkg.name, &pkg.version, problem.to_string());
+                }
+            }
+            for library in &libraries {
+                for problem in policy.check(Some(&library.license)) {
+                    let level = if problem.is_error(options.deny_unknown_licenses) {
+                        Level::Error
+                    } else {
+                        Level::Warning
+                    };
+                    diags.add_for(
+                        level,
+                        &library.crate_name,
+                        &library.crate_version,
+                        format!("bundled {} - {}", library.name, problem),
+                    );
+                }
+            }
+        }
+
+        let provides = bundled
+            .iter()
+            .filter(|pkg| wanted(&options.provide_kinds, &pkg.name, &pkg.version))
+            .map(|pkg| Provide {
+                name: pkg.name.clone(),
+                version: rpmver::from_semver(&pkg.version),
+            })
+            .collect();
+
+        let mut license = LicenseExpr::all(
+            crates
+                .iter()
+                .filter(|c| wanted(&options.license_kinds, &c.name, &c.version))
+                .filter_map(|c| c.license.clone())
+                .chain(
+                    libraries
+                        .iter()
+                        .filter(|l| wanted(&options.license_kinds, &l.crate_name, &l.crate_version))
+                        .map(|l| l.license.clone()),
+                )
+                .collect(),
+        );
+        libraries.retain(|l| wanted(&options.provide_kinds, &l.crate_name, &l.crate_version));
+        if options.simplify || !options.prefer.is_empty() {
+            license = license.map(|license| license.simplify(&options.prefer));
+        }
+
+        Report {
+            provides,
+            crates,
+            license,
+            libraries,
+            kinds,
+            diagnostics: diags.into_vec(),
+        }
+    }
+
+    /// Every provide as written in a spec file: the bundled crates, then the
+    /// native libraries bundled in them.
+    pub fn spec_provides(&self) -> Vec<String> {
+        self.provides
+            .iter()
+            .map(|p| p.to_string())
+            .chain(self.libraries.iter().map(|l| l.to_string()))
+            .collect()
+    }
+
+    /// Did anything happen that must fail the run?
+    pub fn failed(&self) -> bool {
+        self.diagnostics.iter().any(|d| d.level == Level::Error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::policy::Problem;
+
+    #[test]
+    fn filtered_crates_are_still_reconciled() {
+        let lock = LockFile::parse(
+            br#"
+version = 4
+
+[[package]]
+name = "app"
+version = "0.1.0"
+dependencies = ["mid"]
+
+[[package]]
+name = "mid"
+version = "1.0.0"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+dependencies = ["winapi"]
+
+[[package]]
+name = "winapi"
+version = "0.3.9"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+"#,
+        )
+        .unwrap();
+        let vendor = Vendor::from_files(&[
+            (
+                "mid/Cargo.toml",
+                r#"
+[package]
+name = "mid"
+version = "1.0.0"
+license = "MIT"
+
+[target.'cfg(windows)'.dependencies]
+winapi = "0.3"
+"#,
+            ),
+            (
+                "winapi/Cargo.toml",
+                r#"
+[package]
+name = "winapi"
+version = "0.3.9"
+license = "MIT OR Apache-2.0"
+"#,
+            ),
+        ]);
+        let options = Options {
+            strict: true,
+            target: Target::from_triple("x86_64-unknown-linux-gnu"),
+            ..Options::default()
+        };
+
+        let report = Report::generate(&lock, None, Some(&vendor), &options);
+        assert!(!report.failed(), "{:?}", report.diagnostics);
+        let provides: Vec<&str> = report.provides.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(provides, ["mid"]);
+        assert_eq!(report.license.unwrap().to_string(), "MIT");
+    }
+
+    const TWO_CRATES: &[u8] = br#"
+version = 4
+
+[[package]]
+name = "mid"
+version = "1.0.0"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+
+[[package]]
+name = "vend"
+version = "0.3.9"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+"#;
+
+    fn only_mid() -> Vendor {
+        Vendor::from_files(&[(
+            "mid/Cargo.toml",
+            "[package]\nname = \"mid\"\nversion = \"1.0.0\"\nlicense = \"MIT\"\n",
+        )])
+    }
+
+    fn errors(report: &Report) -> Vec<String> {
+        report
+            .diagnostics
+            .iter()
+            .filter(|d| d.level == Level::Error)
+            .map(|d| d.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn crates_missing_from_the_vendor_tree_have_unknown_licenses() {
+        let lock = LockFile::parse(TWO_CRATES).unwrap();
+        let vendor = only_mid();
+        let mut options = Options {
+            policy: Some(Policy::default()),
+            ..Options::default()
+        };
+
+        let report = Report::generate(&lock, None, Some(&vendor), &options);
+        assert!(!report.failed(), "{:?}", report.diagnostics);
+        assert!(report.diagnostics.iter().any(|d| d.level == Level::Warning
+            && d.to_string().contains("vend 0.3.9")
+            && d.message == Problem::Unknown.to_string()));
+
+        options.deny_unknown_licenses = true;
+        let report = Report::generate(&lock, None, Some(&vendor), &options);
+        let errors = errors(&report);
+        assert_eq!(errors.len(), 1, "{:?}", errors);
+        assert!(errors[0].contains("vend 0.3.9"));
+    }
+
+    #[test]
+    fn lists_need_a_vendor_tree() {
+        let lock = LockFile::parse(TWO_CRATES).unwrap();
+        let path = std::env::temp_dir().join(format!("deny-list-{}", std::process::id()));
+        std::fs::write(&path, "GPL-3.0-only\n").unwrap();
+        let policy = Policy::load(None, Some(&path)).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let options = Options {
+            policy: Some(policy),
+            ..Options::default()
+        };
+
+        let report = Report::generate(&lock, None, None, &options);
+        assert!(report.failed());
+        assert_eq!(report.license, None);
+
+        // The SPDX list alone only warns.
+        let options = Options {
+            policy: Some(Policy::default()),
+            ..Options::default()
+        };
+        let report = Report::generate(&lock, None, None, &options);
+        assert!(!report.failed());
+    }
+
+    #[test]
+    fn local_packages_are_not_looked_up_in_the_vendor_tree() {
+        let lock = LockFile::parse(
+            br#"
+version = 4
+
+[[package]]
+name = "app"
+version = "0.1.0"
+dependencies = ["mid", "vend"]
+
+[[package]]
+name = "mid"
+version = "1.0.0"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+
+[[package]]
+name = "vend"
+version = "0.3.9"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+"#,
+        )
+        .unwrap();
+        let vendor = only_mid();
+        let options = Options {
+            include_local: true,
+            policy: Some(Policy::default()),
+            deny_unknown_licenses: true,
+            ..Options::default()
+        };
+
+        let report = Report::generate(&lock, None, Some(&vendor), &options);
+        let provides: Vec<&str> = report.provides.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(provides, ["app", "mid", "vend"]);
+        assert!(
+            !report
+                .diagnostics
+                .iter()
+                .any(|d| d.to_string().contains("app 0.1.0")),
+            "{:?}",
+            report.diagnostics
+        );
+        // Only the missing vendored crate is unknown.
+        assert_eq!(errors(&report).len(), 1);
+    }
+}
@@ -0,0 +1,225 @@
+//! Translate crate versions into RPM versions.
+//!
+//! RPM versions may not contain `-`, and compare differently to SemVer. This
+//! follows the Fedora Rust packaging guidelines, as implemented by rust2rpm:
+//!
+//! * A prerelease `1.0.0-beta.3` becomes `1.0.0~beta.3`, as `~` sorts before
+//!   the release in rpmvercmp, just as a SemVer prerelease does.
+//! * Build metadata `1.0.0+build.5` is kept as `1.0.0+build.5`, `+` being
+//!   valid in RPM versions. SemVer ignores build metadata for precedence,
+//!   whereas rpm sorts it after the plain version, but a crate version is only
+//!   ever published once with or without it.
+//! * Any remaining `-` within the prerelease or build becomes `_`.
+//!
+//! rpmvercmp treats numeric segments as newer than alphabetic ones, the
+//! opposite of SemVer, so `1.0.0-alpha.1` sorts after `1.0.0-alpha.beta` in
+//! RPM. That can't be fixed by translation, and doesn't occur in practice.
+
+use std::cmp::Ordering;
+
+/// Convert a SemVer version from Cargo.lock into a valid RPM version.
+pub fn from_semver(version: &str) -> String {
+    let (version, build) = match version.split_once('+') {
+        Some((v, b)) => (v, Some(b)),
+        None => (version, None),
+    };
+    let (release, pre) = match version.split_once('-') {
+        Some((r, p)) => (r, Some(p)),
+        None => (version, None),
+    };
+
+    let mut rpm = release.to_string();
+    if let Some(pre) = pre {
+        rpm.push('~');
+        rpm.push_str(&pre.replace('-', "_"));
+    }
+    if let Some(build) = build {
+        rpm.push('+');
+        rpm.push_str(&build.replace('-', "_"));
+    }
+    rpm
+}
+
+/// Compare two RPM versions exactly as rpm's `rpmvercmp` does, including the
+/// `~` (sorts before anything) and `^` (sorts after the base, but before
+/// anything else appended) operators.
+pub fn rpmvercmp(a: &str, b: &str) -> Ordering {
+    if a == b {
+        return Ordering::Equal;
+    }
+    let mut one = a.as_bytes();
+    let mut two = b.as_bytes();
+
+    let is_sep = |c: &u8| !c.is_ascii_alphanumeric() && *c != b'~' && *c != b'^';
+
+    loop {
+        while one.first().is_some_and(is_sep) {
+            one = &one[1..];
+        }
+        while two.first().is_some_and(is_sep) {
+            two = &two[1..];
+        }
+
+        // Tilde sorts before everything, including the end of the version.
+        if one.first() == Some(&b'~') || two.first() == Some(&b'~') {
+            if one.first() != Some(&b'~') {
+                return Ordering::Greater;
+            }
+            if two.first() != Some(&b'~') {
+                return Ordering::Less;
+            }
+            one = &one[1..];
+            two = &two[1..];
+            continue;
+        }
+
+        // Caret sorts after the end of the version, but before anything else.
+        if one.first() == Some(&b'^') || two.first() == Some(&b'^') {
+            if one.is_empty() {
+                return Ordering::Less;
+            }
+            if two.is_empty() {
+                return Ordering::Greater;
+            }
+            if one[0] != b'^' {
+                return Ordering::Greater;
+            }
+            if two[0] != b'^' {
+                return Ordering::Less;
+            }
+            one = &one[1..];
+            two = &two[1..];
+            continue;
+        }
+
+        if one.is_empty() || two.is_empty() {
+            break;
+        }
+
+        // Take the next segment, which is all digits or all letters,
+        // depending on the first character of one.
+        let numeric = one[0].is_ascii_digit();
+        let segment = |s: &[u8]| {
+            s.iter()
+                .take_while(|c| {
+                    if numeric {
+                        c.is_ascii_digit()
+                    } else {
+                        c.is_ascii_alphabetic()
+                    }
+                })
+                .count()
+        };
+        let (len_one, len_two) = (segment(one), segment(two));
+        let (seg_one, seg_two) = (&one[..len_one], &two[..len_two]);
+        one = &one[len_one..];
+        two = &two[len_two..];
+
+        // Segments of different types: numeric is always newer.
+        if seg_two.is_empty() {
+            return if numeric {
+                Ordering::Greater
+            } else {
+                Ordering::Less
+            };
+        }
+
+        let ord = if numeric {
+            let seg_one = strip_zeros(seg_one);
+            let seg_two = strip_zeros(seg_two);
+            seg_one
+                .len()
+                .cmp(&seg_two.len())
+                .then_with(|| seg_one.cmp(seg_two))
+        } else {
+            seg_one.cmp(seg_two)
+        };
+        if ord != Ordering::Equal {
+            return ord;
+        }
+    }
+
+    match (one.is_empty(), two.is_empty()) {
+        (true, true) => Ordering::Equal,
+        (false, _) => Ordering::Greater,
+        (_, false) => Ordering::Less,
+    }
+}
+
+fn strip_zeros(s: &[u8]) -> &[u8] {
+    let zeros = s.iter().take_while(|c| **c == b'0').count();
+    &s[zeros..]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn translates_versions() {
+        assert_eq!(from_semver("1.2.3"), "1.2.3");
+        assert_eq!(from_semver("1.0.0-beta.3"), "1.0.0~beta.3");
+        assert_eq!(from_semver("1.0.0-pre-release.1"), "1.0.0~pre_release.1");
+        assert_eq!(from_semver("2.1.1+zstd.1.5.7"), "2.1.1+zstd.1.5.7");
+        assert_eq!(from_semver("0.9.0-rc.1+build-5"), "0.9.0~rc.1+build_5");
+    }
+
+    #[test]
+    fn rpmvercmp_matches_rpm() {
+        // Cases from rpm's own test suite, tests/rpmvercmp.at
+        let cases = [
+            ("1.0", "1.0", Ordering::Equal),
+            ("1.0", "2.0", Ordering::Less),
+            ("2.0.1", "2.0.1a", Ordering::Less),
+            ("5.5p1", "5.5p10", Ordering::Less),
+            ("10xyz", "10.1xyz", Ordering::Less),
+            ("xyz10", "xyz10.1", Ordering::Less),
+            ("1.0aa", "1.0a", Ordering::Greater),
+            ("10.0001", "10.1", Ordering::Equal),
+            ("10.0001", "10.0039", Ordering::Less),
+            ("4.999.9", "5.0", Ordering::Less),
+            ("20101121", "20101122", Ordering::Less),
+            ("2_0", "2_0", Ordering::Equal),
+            ("2.0", "2_0", Ordering::Equal),
+            ("a", "1", Ordering::Less),
+            ("1.0~rc1", "1.0", Ordering::Less),
+            ("1.0~rc1", "1.0~rc2", Ordering::Less),
+            ("1.0~rc1~git123", "1.0~rc1", Ordering::Less),
+            ("1.0^", "1.0", Ordering::Greater),
+            ("1.0^git1", "1.01", Ordering::Less),
+            ("1.0^git1~pre", "1.0^git1", Ordering::Less),
+        ];
+        for (a, b, ord) in cases.iter() {
+            assert_eq!(rpmvercmp(a, b), *ord, "{} vs {}", a, b);
+            assert_eq!(rpmvercmp(b, a), ord.reverse(), "{} vs {}", b, a);
+        }
+    }
+
+    #[test]
+    fn translated_prereleases_sort_like_semver() {
+        // In increasing SemVer precedence.
+        let versions = [
+            "0.9.9",
+            "1.0.0-alpha",
+            "1.0.0-alpha.1",
+            "1.0.0-beta",
+            "1.0.0-beta.2",
+            "1.0.0-beta.11",
+            "1.0.0-rc.1",
+            "1.0.0",
+            "1.0.1-rc.1",
+            "1.0.1",
+            "1.10.0",
+        ];
+        for pair in versions.windows(2) {
+            let (older, newer) = (from_semver(pair[0]), from_semver(pair[1]));
+            assert_eq!(
+                rpmvercmp(&older, &newer),
+                Ordering::Less,
+                "{} should be older than {}",
+                older,
+                newer
+            );
+        }
+    }
+}
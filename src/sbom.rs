@@ -0,0 +1,388 @@
+//! The data shared by the SBOM formats: every locked package with its purl,
+//! download location, checksum and licenses, and the dependency graph
+//! between them.
+
+use crate::graph;
+use crate::license::LicenseExpr;
+use crate::lockfile::{LockFile, LockedPackage, Source};
+use crate::report::Report;
+use crate::spdx;
+use crate::vendored::LicenseSource;
+use crate::workspace::Workspace;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, BTreeSet};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+pub(crate) const TOOL: &str = concat!(env!("CARGO_PKG_NAME"), "-", env!("CARGO_PKG_VERSION"));
+
+/// A locked package, as it appears in an SBOM.
+#[derive(Debug)]
+pub struct Component<'a> {
+    pub package: &'a LockedPackage,
+    /// Unique within the document, `name-version` unless that is ambiguous.
+    pub key: String,
+    pub purl: String,
+    pub download: Option<String>,
+    /// The SHA-256 of the .crate file, for registry packages.
+    pub sha256: Option<&'a str>,
+    /// The `license` field of the crate's Cargo.toml.
+    pub declared: Option<LicenseExpr>,
+    /// The license we determined, however it was found.
+    pub concluded: Option<LicenseExpr>,
+    /// Indexes of the components this one depends on.
+    pub depends_on: Vec<usize>,
+}
+
+/// Everything an SBOM describes, gathered from the lockfile and a report.
+#[derive(Debug)]
+pub struct Sbom<'a> {
+    pub name: String,
+    pub version: String,
+    /// RFC 3339 UTC timestamp, such as `2024-01-01T00:00:00Z`.
+    pub created: String,
+    /// A UUID unique to this document, derived from its contents.
+    pub uuid: String,
+    /// A URI unique to this document.
+    pub namespace: String,
+    pub components: Vec<Component<'a>>,
+    /// Indexes of the workspace members the document describes.
+    pub roots: Vec<usize>,
+}
+
+/// A name based UUIDv8 (RFC 9562) from the SHA-256 of the name, so it
+/// only changes when the name does.
+fn uuid(name: &[u8]) -> String {
+    let mut bytes = [0u8; 16];
+    bytes.copy_from_slice(&Sha256::digest(name)[..16]);
+    bytes[6] = (bytes[6] & 0x0f) | 0x80;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
+
+impl<'a> Sbom<'a> {
+    pub fn new(
+        lock: &'a LockFile,
+        workspace: Option<&Workspace>,
+        report: &Report,
+        created: SystemTime,
+    ) -> Self {
+        let mut seen = BTreeSet::new();
+        let mut components: Vec<Component> = lock
+            .packages
+            .iter()
+            .map(|pkg| {
+                let mut key = format!("{}-{}", pkg.name, pkg.version);
+                let mut n = 1;
+                while !seen.insert(key.clone()) {
+                    n += 1;
+                    key = format!("{}-{}-{}", pkg.name, pkg.version, n);
+                }
+                let krate = report
+                    .crates
+                    .iter()
+                    .find(|c| c.name == pkg.name && c.version == pkg.version);
+                let concluded = krate.and_then(|c| c.license.clone());
+                let declared = krate
+                    .filter(|c| c.license_source == LicenseSource::Declared)
+                    .and(concluded.clone());
+                Component {
+                    package: pkg,
+                    key,
+                    purl: purl(pkg),
+                    download: download(pkg),
+                    sha256: pkg.checksum.as_deref(),
+                    declared,
+                    concluded,
+                    depends_on: Vec::new(),
+                }
+            })
+            .collect();
+
+        for (i, pkg) in lock.packages.iter().enumerate() {
+            components[i].depends_on = pkg
+                .dependencies
+                .iter()
+                .filter_map(|dep| lock.packages.iter().position(|p| p.matches(dep)))
+                .collect();
+        }
+
+        // The workspace members are what the document is about.
+        let roots = graph::roots(lock, workspace);
+
+        let (name, version) = match roots.first() {
+            Some(&i) => (
+                components[i].package.name.clone(),
+                components[i].package.version.clone(),
+            ),
+            None => ("unknown".to_string(), "0.0.0".to_string()),
+        };
+
+        let secs = created
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        // Reproducible, rather than random, so that rebuilding a package
+        // gives the same document.
+        let mut seed = Vec::new();
+        seed.extend_from_slice(&secs.to_be_bytes());
+        for c in &components {
+            seed.extend_from_slice(c.key.as_bytes());
+            seed.push(0);
+            seed.extend_from_slice(c.package.raw_source.as_deref().unwrap_or("").as_bytes());
+            seed.push(0);
+        }
+        let uuid = uuid(&seed);
+        let namespace = format!("https://spdx.org/spdxdocs/{}-{}-{}", name, version, uuid);
+
+        Sbom {
+            name,
+            version,
+            created: rfc3339(secs),
+            uuid,
+            namespace,
+            components,
+            roots,
+        }
+    }
+
+    /// The licenses used in the document that aren't on the SPDX license
+    /// list, with the `LicenseRef-` they are replaced by.
+    pub fn license_refs(&self) -> BTreeMap<String, String> {
+        let mut refs = BTreeMap::new();
+        for c in &self.components {
+            for expr in c.declared.iter().chain(c.concluded.iter()) {
+                collect_refs(expr, &mut refs);
+            }
+        }
+        refs
+    }
+}
+
+/// The creation time of an SBOM. Honours `SOURCE_DATE_EPOCH`, so documents
+/// built in an RPM are reproducible.
+pub fn build_time() -> SystemTime {
+    std::env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|s| s.trim().parse().ok())
+        .map(|secs| UNIX_EPOCH + Duration::from_secs(secs))
+        .unwrap_or_else(SystemTime::now)
+}
+
+/// `pkg:cargo/name@version`, as defined by the purl specification.
+fn purl(pkg: &LockedPackage) -> String {
+    let mut purl = format!("pkg:cargo/{}@{}", pkg.name, pkg.version.replace('+', "%2B"));
+    if let Some(Source::Git { url, precise, .. }) = &pkg.source {
+        purl.push_str("?vcs_url=git%2B");
+        purl.push_str(&url.replace('%', "%25").replace('?', "%3F"));
+        if let Some(rev) = precise {
+            purl.push_str("%40");
+            purl.push_str(rev);
+        }
+    }
+    purl
+}
+
+fn is_crates_io(url: &str) -> bool {
+    url == "https://github.com/rust-lang/crates.io-index"
+        || url == "sparse+https://index.crates.io/"
+}
+
+fn download(pkg: &LockedPackage) -> Option<String> {
+    match &pkg.source {
+        Some(Source::Registry(url)) if is_crates_io(url) => Some(format!(
+            "https://crates.io/api/v1/crates/{}/{}/download",
+            pkg.name, pkg.version
+        )),
+        Some(Source::Git { url, precise, .. }) => Some(match precise {
+            Some(rev) => format!("git+{}@{}", url, rev),
+            None => format!("git+{}", url),
+        }),
+        _ => None,
+    }
+}
+
+fn license_ref(id: &str) -> String {
+    let id: String = id
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '.' || c == '-' {
+                c
+            } else {
+                '-'
+            }
+        })
+        .collect();
+    format!("LicenseRef-{}", id)
+}
+
+fn collect_refs(expr: &LicenseExpr, refs: &mut BTreeMap<String, String>) {
+    match expr {
+        LicenseExpr::License { id, .. } => {
+            if spdx::license(id).is_none() && !spdx::is_license_ref(id) {
+                refs.insert(id.clone(), license_ref(id));
+            }
+        }
+        LicenseExpr::And(terms) | LicenseExpr::Or(terms) => {
+            for term in terms {
+                collect_refs(term, refs);
+            }
+        }
+    }
+}
+
+/// Render an expression using only ids that are valid in an SPDX document,
+/// replacing unknown licenses with a `LicenseRef-`.
+pub fn spdx_expression(expr: &LicenseExpr) -> String {
+    fn replace(expr: &LicenseExpr) -> LicenseExpr {
+        match expr {
+            LicenseExpr::License {
+                id,
+                plus,
+                exception,
+            } if spdx::license(id).is_none() && !spdx::is_license_ref(id) => LicenseExpr::License {
+                id: license_ref(id),
+                plus: *plus,
+                exception: exception.clone(),
+            },
+            LicenseExpr::License { .. } => expr.clone(),
+            LicenseExpr::And(terms) => LicenseExpr::And(terms.iter().map(replace).collect()),
+            LicenseExpr::Or(terms) => LicenseExpr::Or(terms.iter().map(replace).collect()),
+        }
+    }
+    replace(expr).to_string()
+}
+
+/// The UTC (year, month, day) of a number of days since the epoch, using
+/// Howard Hinnant's civil_from_days.
+pub(crate) fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+/// Format seconds since the epoch as an RFC 3339 UTC timestamp.
+fn rfc3339(secs: u64) -> String {
+    let (year, month, day) = civil_from_days((secs / 86400) as i64);
+    let rem = secs % 86400;
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        rem / 3600,
+        rem % 3600 / 60,
+        rem % 60
+    )
+}
+
+/// `app` with three registry dependencies: itoa under a choice of licenses,
+/// ryu under a single one, and `custom` under a license that isn't on the
+/// SPDX list. Shared by the tests of each format.
+#[cfg(test)]
+pub(crate) fn example() -> (LockFile, Report) {
+    use crate::report::Options;
+    use crate::vendor::Vendor;
+
+    let lock = LockFile::parse(
+        br#"
+version = 4
+
+[[package]]
+name = "app"
+version = "0.1.0"
+dependencies = ["custom", "itoa", "ryu"]
+
+[[package]]
+name = "custom"
+version = "2.0.0"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+checksum = "1111111111111111111111111111111111111111111111111111111111111111"
+dependencies = ["ryu"]
+
+[[package]]
+name = "itoa"
+version = "1.0.11"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+checksum = "49f1f14873335454500d59611f1cf4a4b0f786f9ac11f4312a78e4cf2566695b"
+
+[[package]]
+name = "ryu"
+version = "1.0.18"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+checksum = "f3cb5ba0dc43242ce17de99c180e96db90b235b8a9fdc9543c96d2209116bd9f"
+"#,
+    )
+    .unwrap();
+    let vendor = Vendor::from_files(&[
+        (
+            "custom/Cargo.toml",
+            "[package]\nname = \"custom\"\nversion = \"2.0.0\"\nlicense = \"Custom-1.0\"\n",
+        ),
+        (
+            "itoa/Cargo.toml",
+            "[package]\nname = \"itoa\"\nversion = \"1.0.11\"\nlicense = \"MIT OR Apache-2.0\"\n",
+        ),
+        (
+            "ryu/Cargo.toml",
+            "[package]\nname = \"ryu\"\nversion = \"1.0.18\"\nlicense = \"Apache-2.0\"\n",
+        ),
+    ]);
+    let report = Report::generate(&lock, None, Some(&vendor), &Options::default());
+    (lock, report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::report::Options;
+
+    #[test]
+    fn uuid_is_version_8_and_variant_1() {
+        assert_eq!(uuid(b"abc"), "ba7816bf-8f01-8fea-8141-40de5dae2223");
+    }
+
+    #[test]
+    fn document_uuid_is_pinned() {
+        let lock = LockFile::parse(
+            br#"
+version = 4
+
+[[package]]
+name = "app"
+version = "0.1.0"
+dependencies = ["itoa"]
+
+[[package]]
+name = "itoa"
+version = "1.0.11"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+"#,
+        )
+        .unwrap();
+        let report = Report::generate(&lock, None, None, &Options::default());
+        let created = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+
+        let sbom = Sbom::new(&lock, None, &report, created);
+        assert_eq!(sbom.uuid, "62b2cf74-3a60-891d-920f-7a4bc87f0b2c");
+        assert_eq!(sbom.uuid, Sbom::new(&lock, None, &report, created).uuid);
+        assert_eq!(
+            sbom.namespace,
+            format!("https://spdx.org/spdxdocs/app-0.1.0-{}", sbom.uuid)
+        );
+    }
+}
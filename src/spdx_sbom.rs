@@ -0,0 +1,489 @@
+//! SPDX SBOMs, in the 2.3 tag-value and JSON formats and the 3.0 JSON-LD
+//! format.
+
+use crate::license::LicenseExpr;
+use crate::sbom::{spdx_expression, Component, Sbom, TOOL};
+use serde_json::{json, Value};
+use std::fmt::{self, Write};
+
+const NOASSERTION: &str = "NOASSERTION";
+
+fn spdx_id(c: &Component) -> String {
+    let key: String = c
+        .key
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '.' || c == '-' {
+                c
+            } else {
+                '-'
+            }
+        })
+        .collect();
+    format!("SPDXRef-Package-{}", key)
+}
+
+fn license_field(expr: Option<&LicenseExpr>) -> String {
+    expr.map(spdx_expression)
+        .unwrap_or_else(|| NOASSERTION.to_string())
+}
+
+/// An SPDX 2.3 document in the tag-value format.
+pub fn tag_value(sbom: &Sbom) -> String {
+    let mut out = String::new();
+    write_tag_value(sbom, &mut out).expect("Unable to write SBOM");
+    out
+}
+
+fn write_tag_value(sbom: &Sbom, w: &mut String) -> fmt::Result {
+    writeln!(w, "SPDXVersion: SPDX-2.3")?;
+    writeln!(w, "DataLicense: CC0-1.0")?;
+    writeln!(w, "SPDXID: SPDXRef-DOCUMENT")?;
+    writeln!(w, "DocumentName: {}-{}", sbom.name, sbom.version)?;
+    writeln!(w, "DocumentNamespace: {}", sbom.namespace)?;
+    writeln!(w, "Creator: Tool: {}", TOOL)?;
+    writeln!(w, "Created: {}", sbom.created)?;
+
+    for c in &sbom.components {
+        writeln!(w)?;
+        writeln!(w, "PackageName: {}", c.package.name)?;
+        writeln!(w, "SPDXID: {}", spdx_id(c))?;
+        writeln!(w, "PackageVersion: {}", c.package.version)?;
+        writeln!(w, "PackageSupplier: {}", NOASSERTION)?;
+        writeln!(
+            w,
+            "PackageDownloadLocation: {}",
+            c.download.as_deref().unwrap_or(NOASSERTION)
+        )?;
+        writeln!(w, "FilesAnalyzed: false")?;
+        if let Some(sum) = c.sha256 {
+            writeln!(w, "PackageChecksum: SHA256: {}", sum)?;
+        }
+        writeln!(
+            w,
+            "PackageLicenseConcluded: {}",
+            license_field(c.concluded.as_ref())
+        )?;
+        writeln!(
+            w,
+            "PackageLicenseDeclared: {}",
+            license_field(c.declared.as_ref())
+        )?;
+        writeln!(w, "PackageCopyrightText: {}", NOASSERTION)?;
+        writeln!(w, "ExternalRef: PACKAGE-MANAGER purl {}", c.purl)?;
+    }
+
+    for (id, license_ref) in sbom.license_refs() {
+        writeln!(w)?;
+        writeln!(w, "LicenseID: {}", license_ref)?;
+        writeln!(w, "ExtractedText: <text>{}</text>", id)?;
+        writeln!(w, "LicenseName: {}", id)?;
+    }
+
+    writeln!(w)?;
+    for &root in &sbom.roots {
+        writeln!(
+            w,
+            "Relationship: SPDXRef-DOCUMENT DESCRIBES {}",
+            spdx_id(&sbom.components[root])
+        )?;
+    }
+    for c in &sbom.components {
+        for &dep in &c.depends_on {
+            writeln!(
+                w,
+                "Relationship: {} DEPENDS_ON {}",
+                spdx_id(c),
+                spdx_id(&sbom.components[dep])
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// An SPDX 2.3 document in the JSON format.
+pub fn json(sbom: &Sbom) -> String {
+    let packages: Vec<Value> = sbom
+        .components
+        .iter()
+        .map(|c| {
+            let mut pkg = json!({
+                "name": c.package.name,
+                "SPDXID": spdx_id(c),
+                "versionInfo": c.package.version,
+                "supplier": NOASSERTION,
+                "downloadLocation": c.download.as_deref().unwrap_or(NOASSERTION),
+                "filesAnalyzed": false,
+                "licenseConcluded": license_field(c.concluded.as_ref()),
+                "licenseDeclared": license_field(c.declared.as_ref()),
+                "copyrightText": NOASSERTION,
+                "externalRefs": [{
+                    "referenceCategory": "PACKAGE-MANAGER",
+                    "referenceType": "purl",
+                    "referenceLocator": c.purl,
+                }],
+            });
+            if let Some(sum) = c.sha256 {
+                pkg["checksums"] = json!([{ "algorithm": "SHA256", "checksumValue": sum }]);
+            }
+            pkg
+        })
+        .collect();
+
+    let mut relationships: Vec<Value> = sbom
+        .roots
+        .iter()
+        .map(|&root| {
+            json!({
+                "spdxElementId": "SPDXRef-DOCUMENT",
+                "relationshipType": "DESCRIBES",
+                "relatedSpdxElement": spdx_id(&sbom.components[root]),
+            })
+        })
+        .collect();
+    for c in &sbom.components {
+        for &dep in &c.depends_on {
+            relationships.push(json!({
+                "spdxElementId": spdx_id(c),
+                "relationshipType": "DEPENDS_ON",
+                "relatedSpdxElement": spdx_id(&sbom.components[dep]),
+            }));
+        }
+    }
+
+    let extracted: Vec<Value> = sbom
+        .license_refs()
+        .into_iter()
+        .map(|(id, license_ref)| {
+            json!({
+                "licenseId": license_ref,
+                "extractedText": id,
+                "name": id,
+            })
+        })
+        .collect();
+
+    let mut doc = json!({
+        "spdxVersion": "SPDX-2.3",
+        "dataLicense": "CC0-1.0",
+        "SPDXID": "SPDXRef-DOCUMENT",
+        "name": format!("{}-{}", sbom.name, sbom.version),
+        "documentNamespace": sbom.namespace,
+        "creationInfo": {
+            "creators": [format!("Tool: {}", TOOL)],
+            "created": sbom.created,
+        },
+        "packages": packages,
+        "relationships": relationships,
+    });
+    if !extracted.is_empty() {
+        doc["hasExtractedLicensingInfos"] = Value::Array(extracted);
+    }
+    serde_json::to_string_pretty(&doc).expect("Unable to serialise SBOM")
+}
+
+/// An SPDX 3.0 document in the JSON-LD format, using the software and simple
+/// licensing profiles.
+pub fn json3(sbom: &Sbom) -> String {
+    let id = |name: &str| format!("{}#{}", sbom.namespace, name);
+    let element = |kind: &str, spdx_id: String, mut fields: Value| {
+        fields["type"] = json!(kind);
+        fields["spdxId"] = json!(spdx_id);
+        fields["creationInfo"] = json!("_:creationinfo");
+        fields
+    };
+    // Each package has at most one relationship of each kind.
+    let relationship = |from: &str, kind: &str, to: Vec<String>| {
+        element(
+            "Relationship",
+            id(&format!("{}-{}", from, kind)),
+            json!({ "from": id(from), "relationshipType": kind, "to": to }),
+        )
+    };
+
+    let agent = id("SoftwareAgent");
+    let tool = id("Tool");
+    let mut graph = vec![
+        json!({
+            "type": "CreationInfo",
+            "@id": "_:creationinfo",
+            "specVersion": "3.0.1",
+            "created": sbom.created,
+            "createdBy": [agent],
+            "createdUsing": [tool],
+        }),
+        element("SoftwareAgent", agent.clone(), json!({ "name": TOOL })),
+        element("Tool", tool.clone(), json!({ "name": TOOL })),
+    ];
+    let mut elements = Vec::new();
+
+    for c in &sbom.components {
+        let name = spdx_id(c);
+        let pkg_id = id(&name);
+        let mut pkg = json!({
+            "name": c.package.name,
+            "software_packageVersion": c.package.version,
+            "software_packageUrl": c.purl,
+            "externalIdentifier": [{
+                "type": "ExternalIdentifier",
+                "externalIdentifierType": "packageUrl",
+                "identifier": c.purl,
+            }],
+        });
+        if let Some(download) = &c.download {
+            pkg["software_downloadLocation"] = json!(download);
+        }
+        if let Some(sum) = c.sha256 {
+            pkg["verifiedUsing"] = json!([{
+                "type": "Hash",
+                "algorithm": "sha256",
+                "hashValue": sum,
+            }]);
+        }
+        graph.push(element("software_Package", pkg_id.clone(), pkg));
+        elements.push(pkg_id.clone());
+
+        let licenses = [
+            ("hasDeclaredLicense", "Declared", c.declared.as_ref()),
+            ("hasConcludedLicense", "Concluded", c.concluded.as_ref()),
+        ];
+        for (kind, which, expr) in licenses.iter() {
+            if let Some(expr) = expr {
+                let lic_id = id(&format!("{}-License-{}", name, which));
+                graph.push(element(
+                    "simplelicensing_LicenseExpression",
+                    lic_id.clone(),
+                    json!({ "simplelicensing_licenseExpression": spdx_expression(expr) }),
+                ));
+                let rel = relationship(&name, kind, vec![lic_id.clone()]);
+                elements.push(lic_id);
+                elements.push(rel["spdxId"].as_str().unwrap_or_default().to_string());
+                graph.push(rel);
+            }
+        }
+
+        if !c.depends_on.is_empty() {
+            let to = c
+                .depends_on
+                .iter()
+                .map(|&dep| id(&spdx_id(&sbom.components[dep])))
+                .collect();
+            let rel = relationship(&name, "dependsOn", to);
+            elements.push(rel["spdxId"].as_str().unwrap_or_default().to_string());
+            graph.push(rel);
+        }
+    }
+
+    let roots: Vec<String> = sbom
+        .roots
+        .iter()
+        .map(|&root| id(&spdx_id(&sbom.components[root])))
+        .collect();
+    let bom_id = id("SPDXRef-Sbom");
+    graph.push(element(
+        "software_Sbom",
+        bom_id.clone(),
+        json!({
+            "software_sbomType": ["build"],
+            "rootElement": roots,
+            "element": elements,
+        }),
+    ));
+    elements.push(bom_id.clone());
+    graph.push(element(
+        "SpdxDocument",
+        id("SPDXRef-DOCUMENT"),
+        json!({
+            "name": format!("{}-{}", sbom.name, sbom.version),
+            "dataLicense": "https://spdx.org/licenses/CC0-1.0",
+            "profileConformance": ["core", "software", "simpleLicensing"],
+            "rootElement": [bom_id],
+            "element": elements,
+        }),
+    ));
+
+    let doc = json!({
+        "@context": "https://spdx.org/rdf/3.0.1/spdx-context.jsonld",
+        "@graph": graph,
+    });
+    serde_json::to_string_pretty(&doc).expect("Unable to serialise SBOM")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sbom;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    fn created() -> std::time::SystemTime {
+        UNIX_EPOCH + Duration::from_secs(1_700_000_000)
+    }
+
+    #[test]
+    fn tag_value_document() {
+        let (lock, report) = sbom::example();
+        let sbom = Sbom::new(&lock, None, &report, created());
+        let doc = tag_value(&sbom);
+        let lines: Vec<&str> = doc.lines().collect();
+
+        assert_eq!(lines[0], "SPDXVersion: SPDX-2.3");
+        assert!(lines.contains(&"DocumentName: app-0.1.0"));
+        assert!(lines.contains(&format!("DocumentNamespace: {}", sbom.namespace).as_str()));
+        assert!(lines.contains(&"Created: 2023-11-14T22:13:20Z"));
+
+        // Each package is a block starting at its name.
+        let itoa = lines
+            .iter()
+            .position(|l| *l == "PackageName: itoa")
+            .unwrap();
+        assert_eq!(
+            &lines[itoa..itoa + 11],
+            [
+                "PackageName: itoa",
+                "SPDXID: SPDXRef-Package-itoa-1.0.11",
+                "PackageVersion: 1.0.11",
+                "PackageSupplier: NOASSERTION",
+                "PackageDownloadLocation: https://crates.io/api/v1/crates/itoa/1.0.11/download",
+                "FilesAnalyzed: false",
+                "PackageChecksum: SHA256: \
+                 49f1f14873335454500d59611f1cf4a4b0f786f9ac11f4312a78e4cf2566695b",
+                "PackageLicenseConcluded: Apache-2.0 OR MIT",
+                "PackageLicenseDeclared: Apache-2.0 OR MIT",
+                "PackageCopyrightText: NOASSERTION",
+                "ExternalRef: PACKAGE-MANAGER purl pkg:cargo/itoa@1.0.11",
+            ]
+        );
+        assert!(lines.contains(&"PackageLicenseDeclared: LicenseRef-Custom-1.0"));
+        assert!(lines.contains(&"LicenseID: LicenseRef-Custom-1.0"));
+
+        let relationships: Vec<&str> = lines
+            .iter()
+            .filter_map(|l| l.strip_prefix("Relationship: "))
+            .collect();
+        assert_eq!(
+            relationships,
+            [
+                "SPDXRef-DOCUMENT DESCRIBES SPDXRef-Package-app-0.1.0",
+                "SPDXRef-Package-app-0.1.0 DEPENDS_ON SPDXRef-Package-custom-2.0.0",
+                "SPDXRef-Package-app-0.1.0 DEPENDS_ON SPDXRef-Package-itoa-1.0.11",
+                "SPDXRef-Package-app-0.1.0 DEPENDS_ON SPDXRef-Package-ryu-1.0.18",
+                "SPDXRef-Package-custom-2.0.0 DEPENDS_ON SPDXRef-Package-ryu-1.0.18",
+            ]
+        );
+    }
+
+    #[test]
+    fn json_document() {
+        let (lock, report) = sbom::example();
+        let sbom = Sbom::new(&lock, None, &report, created());
+        let doc: Value = serde_json::from_str(&json(&sbom)).unwrap();
+
+        assert_eq!(doc["spdxVersion"], "SPDX-2.3");
+        assert_eq!(doc["SPDXID"], "SPDXRef-DOCUMENT");
+        assert_eq!(doc["name"], "app-0.1.0");
+        assert_eq!(doc["documentNamespace"], sbom.namespace.as_str());
+        assert_eq!(doc["creationInfo"]["created"], "2023-11-14T22:13:20Z");
+
+        let packages = doc["packages"].as_array().unwrap();
+        assert_eq!(packages.len(), 4);
+        let app = &packages[0];
+        assert_eq!(app["downloadLocation"], NOASSERTION);
+        assert_eq!(app["licenseDeclared"], NOASSERTION);
+        assert!(app.get("checksums").is_none());
+        let itoa = &packages[2];
+        assert_eq!(itoa["SPDXID"], "SPDXRef-Package-itoa-1.0.11");
+        assert_eq!(itoa["versionInfo"], "1.0.11");
+        assert_eq!(itoa["licenseConcluded"], "Apache-2.0 OR MIT");
+        assert_eq!(itoa["checksums"][0]["algorithm"], "SHA256");
+        assert_eq!(
+            itoa["externalRefs"][0]["referenceLocator"],
+            "pkg:cargo/itoa@1.0.11"
+        );
+        assert_eq!(
+            doc["hasExtractedLicensingInfos"][0]["licenseId"],
+            "LicenseRef-Custom-1.0"
+        );
+
+        let relationships: Vec<(&str, &str, &str)> = doc["relationships"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|r| {
+                (
+                    r["spdxElementId"].as_str().unwrap(),
+                    r["relationshipType"].as_str().unwrap(),
+                    r["relatedSpdxElement"].as_str().unwrap(),
+                )
+            })
+            .collect();
+        assert_eq!(relationships.len(), 5);
+        assert_eq!(
+            relationships[0],
+            ("SPDXRef-DOCUMENT", "DESCRIBES", "SPDXRef-Package-app-0.1.0")
+        );
+        assert_eq!(
+            relationships[4],
+            (
+                "SPDXRef-Package-custom-2.0.0",
+                "DEPENDS_ON",
+                "SPDXRef-Package-ryu-1.0.18"
+            )
+        );
+    }
+
+    #[test]
+    fn json3_document() {
+        let (lock, report) = sbom::example();
+        let sbom = Sbom::new(&lock, None, &report, created());
+        let doc: Value = serde_json::from_str(&json3(&sbom)).unwrap();
+        let graph = doc["@graph"].as_array().unwrap();
+        let id = |name: &str| format!("{}#{}", sbom.namespace, name);
+        let find = |spdx_id: &str| {
+            graph
+                .iter()
+                .find(|e| e["spdxId"] == spdx_id)
+                .unwrap_or_else(|| panic!("{} is missing", spdx_id))
+        };
+
+        assert_eq!(graph[0]["type"], "CreationInfo");
+        assert_eq!(graph[0]["specVersion"], "3.0.1");
+        assert_eq!(graph[0]["created"], "2023-11-14T22:13:20Z");
+
+        let document = find(&id("SPDXRef-DOCUMENT"));
+        assert_eq!(document["type"], "SpdxDocument");
+        assert_eq!(document["name"], "app-0.1.0");
+        assert_eq!(document["rootElement"], json!([id("SPDXRef-Sbom")]));
+        let bom = find(&id("SPDXRef-Sbom"));
+        assert_eq!(bom["rootElement"], json!([id("SPDXRef-Package-app-0.1.0")]));
+        // Everything but the creation info, agent, tool and document itself.
+        assert_eq!(
+            document["element"].as_array().unwrap().len(),
+            graph.len() - 4
+        );
+
+        let itoa = find(&id("SPDXRef-Package-itoa-1.0.11"));
+        assert_eq!(itoa["type"], "software_Package");
+        assert_eq!(itoa["software_packageVersion"], "1.0.11");
+        assert_eq!(itoa["software_packageUrl"], "pkg:cargo/itoa@1.0.11");
+        assert_eq!(itoa["verifiedUsing"][0]["algorithm"], "sha256");
+
+        let declared = find(&id("SPDXRef-Package-itoa-1.0.11-hasDeclaredLicense"));
+        assert_eq!(declared["from"], id("SPDXRef-Package-itoa-1.0.11"));
+        let license = find(declared["to"][0].as_str().unwrap());
+        assert_eq!(
+            license["simplelicensing_licenseExpression"],
+            "Apache-2.0 OR MIT"
+        );
+
+        let depends = find(&id("SPDXRef-Package-app-0.1.0-dependsOn"));
+        assert_eq!(depends["relationshipType"], "dependsOn");
+        assert_eq!(
+            depends["to"],
+            json!([
+                id("SPDXRef-Package-custom-2.0.0"),
+                id("SPDXRef-Package-itoa-1.0.11"),
+                id("SPDXRef-Package-ryu-1.0.18"),
+            ])
+        );
+    }
+}
@@ -0,0 +1,528 @@
+//! Rewriting the generated tags of an RPM spec file in place.
+//!
+//! The provides live between two marker comments, so they can be refreshed
+//! without touching the rest of the spec:
+//!
+//! ```text
+//! # BEGIN cargo-lock2rpmprovides
+//! Provides:       bundled(crate(serde)) = 1.0.0
+//! # END cargo-lock2rpmprovides
+//! ```
+
+use crate::license::LicenseExpr;
+use crate::native;
+use std::fmt;
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+
+pub const BEGIN_MARKER: &str = "# BEGIN cargo-lock2rpmprovides";
+pub const END_MARKER: &str = "# END cargo-lock2rpmprovides";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SpecError {
+    /// There's no BEGIN marker.
+    NoMarkers,
+    /// A BEGIN marker without a matching END.
+    Unterminated,
+    /// The main package has no License tag to rewrite.
+    NoLicense,
+}
+
+impl fmt::Display for SpecError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SpecError::NoMarkers => write!(f, "no {:?} marker found", BEGIN_MARKER),
+            SpecError::Unterminated => {
+                write!(f, "{:?} has no matching {:?}", BEGIN_MARKER, END_MARKER)
+            }
+            SpecError::NoLicense => write!(f, "no License tag found in the main package"),
+        }
+    }
+}
+
+impl std::error::Error for SpecError {}
+
+/// Split text into lines, keeping their line endings.
+fn lines(text: &str) -> Vec<&str> {
+    text.split_inclusive('\n').collect()
+}
+
+/// The value of a `Tag: value` line, if it is that tag. Tags are case
+/// insensitive in RPM.
+pub(crate) fn tag_value<'a>(line: &'a str, tag: &str) -> Option<&'a str> {
+    let (name, value) = line.split_once(':')?;
+    if name.eq_ignore_ascii_case(tag) {
+        Some(value.trim())
+    } else {
+        None
+    }
+}
+
+/// Does this line start a section after the main package's preamble?
+fn is_section(line: &str) -> bool {
+    let word = line.split_whitespace().next().unwrap_or_default();
+    matches!(
+        word,
+        "%package"
+            | "%description"
+            | "%prep"
+            | "%build"
+            | "%install"
+            | "%check"
+            | "%files"
+            | "%changelog"
+    )
+}
+
+/// The index of the lines between the markers, exclusive of the markers.
+pub(crate) fn region(lines: &[&str]) -> Result<(usize, usize), SpecError> {
+    let begin = lines
+        .iter()
+        .position(|l| l.trim() == BEGIN_MARKER)
+        .ok_or(SpecError::NoMarkers)?;
+    let end = lines[begin + 1..]
+        .iter()
+        .position(|l| l.trim() == END_MARKER)
+        .ok_or(SpecError::Unterminated)?;
+    Ok((begin + 1, begin + 1 + end))
+}
+
+/// The index of the License tag of the main package.
+pub(crate) fn license_line(lines: &[&str]) -> Option<usize> {
+    lines
+        .iter()
+        .take_while(|l| !is_section(l))
+        .position(|l| tag_value(l, "License").is_some())
+}
+
+/// Replace the provides between the markers, and the License tag if a
+/// license is given. Everything else is kept byte for byte.
+pub fn update(text: &str, provides: &[String], license: Option<&str>) -> Result<String, SpecError> {
+    let lines = lines(text);
+    let (start, end) = region(&lines)?;
+    let license_at = match license {
+        Some(_) => Some(license_line(&lines).ok_or(SpecError::NoLicense)?),
+        None => None,
+    };
+
+    // Keep the style of the spec: its line endings, and the alignment of the
+    // existing provides.
+    let eol = if text.contains("\r\n") { "\r\n" } else { "\n" };
+    let prefix = lines[start..end]
+        .iter()
+        .find(|l| tag_value(l, "Provides").is_some())
+        .and_then(|l| {
+            let colon = l.find(':')?;
+            let value = l[colon + 1..].len() - l[colon + 1..].trim_start().len();
+            Some(l[..colon + 1 + value].to_string())
+        })
+        .unwrap_or_else(|| "Provides: ".to_string());
+
+    let mut out = String::with_capacity(text.len());
+    for (i, line) in lines.iter().enumerate() {
+        if i == start {
+            for provide in provides {
+                out.push_str(&prefix);
+                out.push_str(provide);
+                out.push_str(eol);
+            }
+        }
+        if i >= start && i < end {
+            continue;
+        }
+        match (license_at, license) {
+            (Some(at), Some(license)) if at == i => {
+                let colon = line.find(':').unwrap_or_default();
+                let rest = &line[colon + 1..];
+                let space = rest.len() - rest.trim_start().len();
+                let ending = &line[line.trim_end_matches(&['\r', '\n'][..]).len()..];
+                out.push_str(&line[..colon + 1 + space]);
+                out.push_str(license);
+                out.push_str(ending);
+            }
+            _ => out.push_str(line),
+        }
+    }
+    Ok(out)
+}
+
+/// Replace a file with new contents, so that readers never see it half
+/// written. The permissions of the original are kept.
+pub fn write_atomic(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let name = path
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "not a file"))?;
+    let mut tmp_name = std::ffi::OsString::from(".");
+    tmp_name.push(name);
+    tmp_name.push(".tmp");
+    let tmp = dir.join(tmp_name);
+
+    let result = (|| {
+        let mut file = fs::File::create(&tmp)?;
+        file.write_all(contents)?;
+        if let Ok(meta) = fs::metadata(path) {
+            file.set_permissions(meta.permissions())?;
+        }
+        file.sync_all()?;
+        fs::rename(&tmp, path)
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    result
+}
+
+/// Update the provides, and the License tag if one is given, of a spec file
+/// on disk. The file is only rewritten if anything changed.
+pub fn update_file(path: &Path, provides: &[String], license: Option<&str>) -> io::Result<()> {
+    let text = fs::read_to_string(path)?;
+    let updated = update(&text, provides, license)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    if updated != text {
+        write_atomic(path, updated.as_bytes())?;
+    }
+    Ok(())
+}
+
+/// The bundled crate and library provides, and the main License tag found in
+/// a spec file.
+fn bundled_tags(text: &str) -> (Vec<String>, Option<String>) {
+    let lines = lines(text);
+    let provides = lines
+        .iter()
+        .filter_map(|l| tag_value(l, "Provides"))
+        .filter(|v| v.starts_with("bundled(crate(") || native::is_library_provide(v))
+        .map(|v| v.split_whitespace().collect::<Vec<_>>().join(" "))
+        .collect();
+    let license = license_line(&lines)
+        .and_then(|i| tag_value(lines[i], "License"))
+        .map(str::to_string);
+    (provides, license)
+}
+
+/// Compare the provides and License tag of a spec file with what they should
+/// be. Returns a unified diff from the spec to the expected tags, or None if
+/// they match.
+pub fn check(name: &str, text: &str, provides: &[String], license: Option<&str>) -> Option<String> {
+    let (mut found, found_license) = bundled_tags(text);
+    let mut expected = provides.to_vec();
+    found.sort();
+    expected.sort();
+
+    // Licenses are equal if they are the same expression, however they are
+    // written.
+    let same_license = match (&found_license, license) {
+        (Some(a), Some(b)) => match (LicenseExpr::parse(a), LicenseExpr::parse(b)) {
+            (Ok(a), Ok(b)) => a == b,
+            _ => a == b,
+        },
+        (None, None) => true,
+        _ => false,
+    };
+
+    let mut old: Vec<String> = found_license
+        .map(|l| format!("License: {}", l))
+        .into_iter()
+        .collect();
+    let mut new = if same_license {
+        old.clone()
+    } else {
+        license
+            .map(|l| format!("License: {}", l))
+            .into_iter()
+            .collect()
+    };
+    old.extend(found.iter().map(|p| format!("Provides: {}", p)));
+    new.extend(expected.iter().map(|p| format!("Provides: {}", p)));
+
+    if old == new {
+        None
+    } else {
+        Some(unified_diff(name, "expected", &old, &new))
+    }
+}
+
+/// [`check`] a spec file on disk.
+pub fn check_file(
+    path: &Path,
+    provides: &[String],
+    license: Option<&str>,
+) -> io::Result<Option<String>> {
+    let text = fs::read_to_string(path)?;
+    Ok(check(&path.to_string_lossy(), &text, provides, license))
+}
+
+enum Edit<'a> {
+    Keep(&'a str),
+    Remove(&'a str),
+    Add(&'a str),
+}
+
+/// A unified diff with three lines of context, from the longest common
+/// subsequence of the two.
+fn unified_diff(old_name: &str, new_name: &str, old: &[String], new: &[String]) -> String {
+    let (n, m) = (old.len(), new.len());
+    let mut lcs = vec![vec![0usize; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lcs[i][j] = if old[i] == new[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut edits = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < n || j < m {
+        if i < n && j < m && old[i] == new[j] {
+            edits.push(Edit::Keep(&old[i]));
+            i += 1;
+            j += 1;
+        } else if i < n && (j == m || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            edits.push(Edit::Remove(&old[i]));
+            i += 1;
+        } else {
+            edits.push(Edit::Add(&new[j]));
+            j += 1;
+        }
+    }
+
+    const CONTEXT: usize = 3;
+    let changed: Vec<usize> = (0..edits.len())
+        .filter(|&k| !matches!(edits[k], Edit::Keep(_)))
+        .collect();
+
+    let mut out = format!("--- {}\n+++ {}\n", old_name, new_name);
+    let mut k = 0;
+    while k < changed.len() {
+        // Group changes whose context overlaps into one hunk.
+        let start = changed[k].saturating_sub(CONTEXT);
+        let mut last = changed[k];
+        while k + 1 < changed.len() && changed[k + 1] <= last + 2 * CONTEXT {
+            k += 1;
+            last = changed[k];
+        }
+        let end = (last + CONTEXT + 1).min(edits.len());
+        k += 1;
+
+        // Line numbers at the start of the hunk.
+        let before = |kind: fn(&Edit) -> bool| edits[..start].iter().filter(|e| kind(e)).count();
+        let old_start = before(|e| !matches!(e, Edit::Add(_)));
+        let new_start = before(|e| !matches!(e, Edit::Remove(_)));
+        let hunk = &edits[start..end];
+        let old_len = hunk.iter().filter(|e| !matches!(e, Edit::Add(_))).count();
+        let new_len = hunk
+            .iter()
+            .filter(|e| !matches!(e, Edit::Remove(_)))
+            .count();
+        out.push_str(&format!(
+            "@@ -{},{} +{},{} @@\n",
+            if old_len == 0 {
+                old_start
+            } else {
+                old_start + 1
+            },
+            old_len,
+            if new_len == 0 {
+                new_start
+            } else {
+                new_start + 1
+            },
+            new_len
+        ));
+        for edit in hunk {
+            match edit {
+                Edit::Keep(l) => out.push_str(&format!(" {}\n", l)),
+                Edit::Remove(l) => out.push_str(&format!("-{}\n", l)),
+                Edit::Add(l) => out.push_str(&format!("+{}\n", l)),
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SPEC: &str = "\
+Name:           foo
+Version:        1.0
+License:        MIT
+# BEGIN cargo-lock2rpmprovides
+Provides:       bundled(crate(old)) = 0.1.0
+# END cargo-lock2rpmprovides
+
+%package devel
+License:        Apache-2.0
+
+%description
+";
+
+    fn strings(v: &[&str]) -> Vec<String> {
+        v.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn update_keeps_alignment() {
+        let provides = strings(&["bundled(crate(a)) = 1.0.0", "bundled(crate(b)) = 2.0.0"]);
+        let out = update(SPEC, &provides, Some("Apache-2.0 OR MIT")).unwrap();
+        assert_eq!(
+            out,
+            "\
+Name:           foo
+Version:        1.0
+License:        Apache-2.0 OR MIT
+# BEGIN cargo-lock2rpmprovides
+Provides:       bundled(crate(a)) = 1.0.0
+Provides:       bundled(crate(b)) = 2.0.0
+# END cargo-lock2rpmprovides
+
+%package devel
+License:        Apache-2.0
+
+%description
+"
+        );
+    }
+
+    #[test]
+    fn update_without_license_leaves_it_alone() {
+        let out = update(SPEC, &[], None).unwrap();
+        assert!(out.contains("License:        MIT\n"));
+        assert!(out.contains("# BEGIN cargo-lock2rpmprovides\n# END cargo-lock2rpmprovides\n"));
+    }
+
+    #[test]
+    fn update_keeps_crlf() {
+        let spec = SPEC.replace('\n', "\r\n");
+        let provides = strings(&["bundled(crate(a)) = 1.0.0"]);
+        let out = update(&spec, &provides, Some("Zlib")).unwrap();
+        assert_eq!(out.matches('\n').count(), out.matches("\r\n").count());
+        assert!(out.contains("License:        Zlib\r\n"));
+        assert!(out.contains("Provides:       bundled(crate(a)) = 1.0.0\r\n"));
+    }
+
+    #[test]
+    fn update_of_an_empty_region_uses_a_default_prefix() {
+        let spec = "License: MIT\n# BEGIN cargo-lock2rpmprovides\n# END cargo-lock2rpmprovides\n";
+        let provides = strings(&["bundled(crate(a)) = 1.0.0"]);
+        assert_eq!(
+            update(spec, &provides, None).unwrap(),
+            "License: MIT\n# BEGIN cargo-lock2rpmprovides\nProvides: bundled(crate(a)) = 1.0.0\n\
+             # END cargo-lock2rpmprovides\n"
+        );
+    }
+
+    #[test]
+    fn update_errors() {
+        assert_eq!(update("Name: foo\n", &[], None), Err(SpecError::NoMarkers));
+        assert_eq!(
+            update("# BEGIN cargo-lock2rpmprovides\nProvides: x\n", &[], None),
+            Err(SpecError::Unterminated)
+        );
+        // A License in a subpackage isn't the main package's.
+        let spec = "\
+# BEGIN cargo-lock2rpmprovides
+# END cargo-lock2rpmprovides
+%package devel
+License: MIT
+";
+        assert_eq!(update(spec, &[], Some("MIT")), Err(SpecError::NoLicense));
+        assert_eq!(update(spec, &[], None).unwrap(), spec);
+    }
+
+    #[test]
+    fn check_matches_equivalent_licenses() {
+        let provides = strings(&["bundled(crate(old)) = 0.1.0"]);
+        assert_eq!(check("foo.spec", SPEC, &provides, Some("MIT")), None);
+        let spec = SPEC.replace("License:        MIT", "License:        MIT OR Apache-2.0");
+        assert_eq!(
+            check("foo.spec", &spec, &provides, Some("Apache-2.0 OR MIT")),
+            None
+        );
+    }
+
+    #[test]
+    fn check_diff() {
+        let provides = strings(&["bundled(crate(new)) = 1.0.0"]);
+        assert_eq!(
+            check("foo.spec", SPEC, &provides, Some("Zlib")).unwrap(),
+            "\
+--- foo.spec
++++ expected
+@@ -1,2 +1,2 @@
+-License: MIT
+-Provides: bundled(crate(old)) = 0.1.0
++License: Zlib
++Provides: bundled(crate(new)) = 1.0.0
+"
+        );
+    }
+
+    #[test]
+    fn update_and_check_a_file() {
+        let path = std::env::temp_dir().join(format!("foo-{}.spec", std::process::id()));
+        fs::write(&path, SPEC).unwrap();
+        let provides = strings(&["bundled(crate(new)) = 1.0.0"]);
+        assert!(check_file(&path, &provides, Some("MIT")).unwrap().is_some());
+        update_file(&path, &provides, None).unwrap();
+        assert_eq!(check_file(&path, &provides, Some("MIT")).unwrap(), None);
+
+        fs::write(&path, "Name: foo\n").unwrap();
+        let err = update_file(&path, &provides, None).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn diff_hunks() {
+        let old: Vec<String> = (1..=20).map(|i| i.to_string()).collect();
+        let mut new = old.clone();
+        new[1] = "two".to_string();
+        new.remove(15);
+        assert_eq!(
+            unified_diff("a", "b", &old, &new),
+            "\
+--- a
++++ b
+@@ -1,5 +1,5 @@
+ 1
+-2
++two
+ 3
+ 4
+ 5
+@@ -13,7 +13,6 @@
+ 13
+ 14
+ 15
+-16
+ 17
+ 18
+ 19
+"
+        );
+
+        // Changes within twice the context of each other share a hunk.
+        let mut near = old.clone();
+        near[1] = "two".to_string();
+        near[7] = "eight".to_string();
+        assert_eq!(
+            unified_diff("a", "b", &old, &near).matches("@@ -").count(),
+            1
+        );
+
+        assert_eq!(
+            unified_diff("a", "b", &[], &strings(&["x"])),
+            "--- a\n+++ b\n@@ -0,0 +1,1 @@\n+x\n"
+        );
+    }
+}
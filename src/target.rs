@@ -0,0 +1,485 @@
+//! Evaluate the `cfg(...)` expressions of platform specific dependencies for
+//! a target triple.
+//!
+//! Only Linux targets are described, as those are the ones we package for.
+//! Any `arch-vendor-linux-env` triple with a known architecture works, so
+//! distribution triples such as `x86_64-redhat-linux-gnu` and
+//! `x86_64-suse-linux` are understood too. The distribution's rustc builds
+//! for the matching Rust triple, here `x86_64-unknown-linux-gnu`, so that is
+//! what the cfgs describe.
+
+use std::fmt;
+
+pub const DEFAULT_TRIPLE: &str = "x86_64-unknown-linux-gnu";
+
+/// The common Linux targets, as listed by `rustc --print target-list`.
+pub const TRIPLES: &[&str] = &[
+    "x86_64-unknown-linux-gnu",
+    "x86_64-unknown-linux-musl",
+    "i686-unknown-linux-gnu",
+    "aarch64-unknown-linux-gnu",
+    "aarch64-unknown-linux-musl",
+    "armv7-unknown-linux-gnueabihf",
+    "arm-unknown-linux-gnueabihf",
+    "powerpc64le-unknown-linux-gnu",
+    "powerpc64-unknown-linux-gnu",
+    "powerpc-unknown-linux-gnu",
+    "s390x-unknown-linux-gnu",
+    "riscv64gc-unknown-linux-gnu",
+    "loongarch64-unknown-linux-gnu",
+    "sparc64-unknown-linux-gnu",
+    "mips64el-unknown-linux-gnuabi64",
+];
+
+/// (triple arch prefix, target_arch, pointer width, big endian, has 64 bit atomics)
+const ARCHES: &[(&str, &str, &str, bool, bool)] = &[
+    ("x86_64", "x86_64", "64", false, true),
+    ("i686", "x86", "32", false, true),
+    ("i586", "x86", "32", false, true),
+    ("aarch64_be", "aarch64", "64", true, true),
+    ("aarch64", "aarch64", "64", false, true),
+    ("armv7", "arm", "32", false, true),
+    ("armv6", "arm", "32", false, true),
+    ("armv5te", "arm", "32", false, false),
+    ("arm", "arm", "32", false, true),
+    ("powerpc64le", "powerpc64", "64", false, true),
+    ("powerpc64", "powerpc64", "64", true, true),
+    ("powerpc", "powerpc", "32", true, false),
+    ("s390x", "s390x", "64", true, true),
+    ("riscv64", "riscv64", "64", false, true),
+    ("loongarch64", "loongarch64", "64", false, true),
+    ("sparc64", "sparc64", "64", true, true),
+    ("mips64el", "mips64", "64", false, true),
+    ("mips64", "mips64", "64", true, true),
+    ("mipsel", "mips", "32", false, false),
+    ("mips", "mips", "32", true, false),
+];
+
+/// RPM architecture names, as used in %{_target_platform}, that differ from
+/// the Rust ones.
+const RPM_ARCHES: &[(&str, &str)] = &[
+    ("ppc64le", "powerpc64le"),
+    ("ppc64", "powerpc64"),
+    ("ppc", "powerpc"),
+    ("i386", "i686"),
+    ("riscv64", "riscv64gc"),
+    ("armv7hl", "armv7"),
+    ("armv7hnl", "armv7"),
+];
+
+/// The Rust triple of a Linux target triple. Distributions use their own
+/// vendor, RPM names some architectures differently, openSUSE leaves out
+/// the environment and armv7hl is hard float, which Rust puts in the ABI.
+fn rust_triple(triple: &str) -> Option<String> {
+    let parts: Vec<&str> = triple.split('-').collect();
+    let (arch, env) = match parts.as_slice() {
+        [arch, _, "linux", env] | [arch, "linux", env] => (*arch, *env),
+        [arch, _, "linux"] => (*arch, "gnu"),
+        _ => return None,
+    };
+    let rust_arch = RPM_ARCHES
+        .iter()
+        .find(|(rpm, _)| *rpm == arch)
+        .map_or(arch, |(_, rust)| *rust);
+    let env = if arch.starts_with("armv7h") && !env.ends_with("hf") {
+        format!("{}eabihf", env.trim_end_matches("eabi"))
+    } else {
+        env.to_string()
+    };
+    Some(format!("{}-unknown-linux-{}", rust_arch, env))
+}
+
+/// A `cfg(...)` predicate.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Cfg {
+    /// A bare name, such as `unix`.
+    Name(String),
+    /// A key and value, such as `target_os = "linux"`.
+    KeyPair(String, String),
+    All(Vec<Cfg>),
+    Any(Vec<Cfg>),
+    Not(Box<Cfg>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CfgError(String);
+
+impl fmt::Display for CfgError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid cfg expression - {}", self.0)
+    }
+}
+
+impl std::error::Error for CfgError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Ident(String),
+    Str(String),
+    Open,
+    Close,
+    Comma,
+    Equals,
+}
+
+fn tokenise(s: &str) -> Result<Vec<Token>, CfgError> {
+    let mut tokens = Vec::new();
+    let mut chars = s.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '(' | ')' | ',' | '=' => {
+                chars.next();
+                tokens.push(match c {
+                    '(' => Token::Open,
+                    ')' => Token::Close,
+                    ',' => Token::Comma,
+                    _ => Token::Equals,
+                });
+            }
+            '"' => {
+                chars.next();
+                let mut value = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some(c) => value.push(c),
+                        None => return Err(CfgError("unterminated string".to_string())),
+                    }
+                }
+                tokens.push(Token::Str(value));
+            }
+            c if c.is_alphanumeric() || c == '_' => {
+                let mut ident = String::new();
+                while let Some(&c) = chars.peek() {
+                    if !(c.is_alphanumeric() || c == '_') {
+                        break;
+                    }
+                    ident.push(c);
+                    chars.next();
+                }
+                tokens.push(Token::Ident(ident));
+            }
+            c => return Err(CfgError(format!("unexpected character {:?}", c))),
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn next(&mut self) -> Option<Token> {
+        let t = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        t
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn expect(&mut self, token: Token) -> Result<(), CfgError> {
+        match self.next() {
+            Some(t) if t == token => Ok(()),
+            t => Err(CfgError(format!("expected {:?}, found {:?}", token, t))),
+        }
+    }
+
+    fn list(&mut self) -> Result<Vec<Cfg>, CfgError> {
+        self.expect(Token::Open)?;
+        let mut items = Vec::new();
+        loop {
+            if self.peek() == Some(&Token::Close) {
+                self.next();
+                return Ok(items);
+            }
+            items.push(self.predicate()?);
+            match self.next() {
+                Some(Token::Comma) => {}
+                Some(Token::Close) => return Ok(items),
+                t => return Err(CfgError(format!("expected , or ), found {:?}", t))),
+            }
+        }
+    }
+
+    fn predicate(&mut self) -> Result<Cfg, CfgError> {
+        let ident = match self.next() {
+            Some(Token::Ident(ident)) => ident,
+            t => return Err(CfgError(format!("expected a name, found {:?}", t))),
+        };
+        match (ident.as_str(), self.peek()) {
+            ("all", Some(Token::Open)) => Ok(Cfg::All(self.list()?)),
+            ("any", Some(Token::Open)) => Ok(Cfg::Any(self.list()?)),
+            ("not", Some(Token::Open)) => {
+                let mut items = self.list()?;
+                if items.len() != 1 {
+                    return Err(CfgError("not() takes exactly one predicate".to_string()));
+                }
+                Ok(Cfg::Not(Box::new(items.remove(0))))
+            }
+            (_, Some(Token::Equals)) => {
+                self.next();
+                match self.next() {
+                    Some(Token::Str(value)) => Ok(Cfg::KeyPair(ident, value)),
+                    t => Err(CfgError(format!("expected a string, found {:?}", t))),
+                }
+            }
+            _ => Ok(Cfg::Name(ident)),
+        }
+    }
+}
+
+impl Cfg {
+    /// Parse the inside of a `cfg(...)`.
+    pub fn parse(s: &str) -> Result<Self, CfgError> {
+        let mut parser = Parser {
+            tokens: tokenise(s)?,
+            pos: 0,
+        };
+        let cfg = parser.predicate()?;
+        match parser.next() {
+            None => Ok(cfg),
+            Some(t) => Err(CfgError(format!("unexpected {:?}", t))),
+        }
+    }
+}
+
+/// The cfg values rustc sets for a target.
+#[derive(Debug, Clone)]
+pub struct Target {
+    pub triple: String,
+    /// The triple as Rust spells it, `arch-unknown-linux-env`, which the
+    /// cfgs describe.
+    normalised: String,
+    cfgs: Vec<(String, Option<String>)>,
+}
+
+impl Target {
+    /// Describe a Linux target triple. Returns None for other operating
+    /// systems, or an unknown architecture.
+    pub fn from_triple(triple: &str) -> Option<Self> {
+        let normalised = rust_triple(triple)?;
+        let parts: Vec<&str> = normalised.split('-').collect();
+        let (arch, env) = match parts.as_slice() {
+            [arch, "unknown", "linux", env] => (*arch, *env),
+            _ => return None,
+        };
+        let &(_, target_arch, width, big_endian, atomic64) = ARCHES
+            .iter()
+            .find(|(prefix, ..)| arch.starts_with(prefix))?;
+
+        let (target_env, abi) = ["gnu", "musl", "uclibc"]
+            .iter()
+            .find_map(|e| env.strip_prefix(e).map(|abi| (*e, abi)))?;
+
+        let mut cfgs: Vec<(String, Option<String>)> = vec![
+            ("unix".into(), None),
+            ("target_family".into(), Some("unix".into())),
+            ("target_os".into(), Some("linux".into())),
+            ("target_vendor".into(), Some("unknown".into())),
+            ("target_env".into(), Some(target_env.into())),
+            ("target_abi".into(), Some(abi.into())),
+            ("target_arch".into(), Some(target_arch.into())),
+            ("target_pointer_width".into(), Some(width.into())),
+            (
+                "target_endian".into(),
+                Some(if big_endian { "big" } else { "little" }.into()),
+            ),
+            ("panic".into(), Some("unwind".into())),
+        ];
+        let mut atomics = vec!["8", "16", "32", "ptr"];
+        if atomic64 {
+            atomics.push("64");
+        }
+        for width in atomics {
+            cfgs.push(("target_has_atomic".into(), Some(width.into())));
+        }
+        if target_arch == "x86_64" || target_arch == "x86" {
+            for feature in ["fxsr", "sse", "sse2"].iter() {
+                cfgs.push(("target_feature".into(), Some(feature.to_string())));
+            }
+        }
+
+        Some(Target {
+            triple: triple.to_string(),
+            normalised,
+            cfgs,
+        })
+    }
+
+    pub fn matches(&self, cfg: &Cfg) -> bool {
+        match cfg {
+            Cfg::Name(name) => self.cfgs.iter().any(|(k, v)| k == name && v.is_none()),
+            Cfg::KeyPair(key, value) => self
+                .cfgs
+                .iter()
+                .any(|(k, v)| k == key && v.as_deref() == Some(value.as_str())),
+            Cfg::All(cfgs) => cfgs.iter().all(|c| self.matches(c)),
+            Cfg::Any(cfgs) => cfgs.iter().any(|c| self.matches(c)),
+            Cfg::Not(cfg) => !self.matches(cfg),
+        }
+    }
+
+    /// Is a `[target.<platform>]` table of a manifest for this target? The
+    /// platform is either `cfg(...)` or a triple, which is normally the Rust
+    /// one even where ours is a distribution's.
+    pub fn matches_platform(&self, platform: &str) -> Result<bool, CfgError> {
+        match platform
+            .trim()
+            .strip_prefix("cfg(")
+            .and_then(|p| p.strip_suffix(')'))
+        {
+            Some(cfg) => Cfg::parse(cfg).map(|cfg| self.matches(&cfg)),
+            None => Ok(platform.trim() == self.triple || platform.trim() == self.normalised),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn target(triple: &str) -> Target {
+        Target::from_triple(triple).unwrap()
+    }
+
+    fn matches(triple: &str, cfg: &str) -> bool {
+        target(triple).matches(&Cfg::parse(cfg).unwrap())
+    }
+
+    #[test]
+    fn empty_all_and_any() {
+        let linux = "x86_64-unknown-linux-gnu";
+        assert_eq!(Cfg::parse("all()"), Ok(Cfg::All(Vec::new())));
+        assert!(matches(linux, "all()"));
+        assert!(!matches(linux, "any()"));
+        assert!(matches(linux, "not(any())"));
+    }
+
+    #[test]
+    fn nested_predicates() {
+        let linux = "x86_64-unknown-linux-gnu";
+        assert!(matches(linux, "unix"));
+        assert!(!matches(linux, "windows"));
+        assert!(matches(linux, r#"all(unix, target_arch = "x86_64",)"#));
+        assert!(matches(
+            linux,
+            r#"any(windows, all(target_os = "linux", not(target_env = "musl")))"#
+        ));
+        assert!(!matches(
+            "x86_64-unknown-linux-musl",
+            r#"not(target_env = "musl")"#
+        ));
+        assert!(matches(
+            "s390x-unknown-linux-gnu",
+            r#"all(target_endian = "big", target_pointer_width = "64")"#
+        ));
+        assert!(!matches(
+            "powerpc-unknown-linux-gnu",
+            r#"target_has_atomic = "64""#
+        ));
+    }
+
+    #[test]
+    fn not_takes_one_predicate() {
+        assert!(Cfg::parse("not()").is_err());
+        assert!(Cfg::parse("not(unix, windows)").is_err());
+        assert_eq!(
+            Cfg::parse("not(unix)"),
+            Ok(Cfg::Not(Box::new(Cfg::Name("unix".to_string()))))
+        );
+    }
+
+    #[test]
+    fn strings() {
+        // Strings have no escapes, everything up to the next quote is kept.
+        assert_eq!(
+            Cfg::parse(r#"feature = "a, b(c) = \""#),
+            Ok(Cfg::KeyPair(
+                "feature".to_string(),
+                "a, b(c) = \\".to_string()
+            ))
+        );
+        assert!(Cfg::parse(r#"target_os = "linux"#).is_err());
+        assert!(Cfg::parse(r#"target_os = linux"#).is_err());
+    }
+
+    #[test]
+    fn invalid_expressions() {
+        for cfg in [
+            "",
+            "unix windows",
+            "all(unix",
+            "any(,)",
+            "unix)",
+            "target_os =",
+        ]
+        .iter()
+        {
+            assert!(Cfg::parse(cfg).is_err(), "{:?}", cfg);
+        }
+    }
+
+    #[test]
+    fn distribution_triples() {
+        let cases = [
+            ("x86_64-redhat-linux-gnu", "x86_64", "gnu", ""),
+            ("x86_64-suse-linux", "x86_64", "gnu", ""),
+            ("ppc64le-suse-linux", "powerpc64", "gnu", ""),
+            ("i386-redhat-linux-gnu", "x86", "gnu", ""),
+            ("aarch64-linux-gnu", "aarch64", "gnu", ""),
+            ("armv7hl-redhat-linux-gnueabi", "arm", "gnu", "eabihf"),
+            ("armv7hl-suse-linux-gnueabi", "arm", "gnu", "eabihf"),
+            ("x86_64-alpine-linux-musl", "x86_64", "musl", ""),
+        ];
+        for (triple, arch, env, abi) in cases.iter() {
+            let cfg = format!(
+                r#"all(target_arch = "{}", target_vendor = "unknown", target_env = "{}", target_abi = "{}")"#,
+                arch, env, abi
+            );
+            assert!(matches(triple, &cfg), "{} should match {}", triple, cfg);
+        }
+        assert!(!matches("x86_64-suse-linux", r#"target_vendor = "suse""#));
+        for triple in TRIPLES {
+            assert!(Target::from_triple(triple).is_some(), "{}", triple);
+        }
+        assert!(Target::from_triple("x86_64-pc-windows-msvc").is_none());
+        assert!(Target::from_triple("x86_64-apple-darwin").is_none());
+        assert!(Target::from_triple("m68k-unknown-linux-gnu").is_none());
+    }
+
+    #[test]
+    fn platform_triples() {
+        let suse = target("x86_64-suse-linux");
+        assert_eq!(suse.matches_platform("x86_64-suse-linux"), Ok(true));
+        assert_eq!(suse.matches_platform("x86_64-unknown-linux-gnu"), Ok(true));
+        assert_eq!(
+            suse.matches_platform("x86_64-unknown-linux-musl"),
+            Ok(false)
+        );
+        assert_eq!(suse.matches_platform("cfg(unix)"), Ok(true));
+        assert!(suse.matches_platform("cfg(unix windows)").is_err());
+
+        let arm = target("armv7hl-redhat-linux-gnueabi");
+        assert_eq!(
+            arm.matches_platform("armv7-unknown-linux-gnueabihf"),
+            Ok(true)
+        );
+
+        let ppc = target("ppc64le-redhat-linux-gnu");
+        assert_eq!(
+            ppc.matches_platform("powerpc64le-unknown-linux-gnu"),
+            Ok(true)
+        );
+        assert_eq!(
+            ppc.matches_platform("powerpc64-unknown-linux-gnu"),
+            Ok(false)
+        );
+    }
+}
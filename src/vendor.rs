@@ -0,0 +1,373 @@
+use crate::detect;
+use crate::manifest::{Manifest, ManifestError};
+use crate::native;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::File;
+use std::io::{self, BufReader, Read};
+use std::path::{Component, Path, PathBuf};
+
+/// A tree of vendored crates, as produced by `cargo vendor`.
+///
+/// Depending on `--versioned-dirs` crates are stored as `name-version/` or
+/// `name/`, and a plain vendor tree will fall back to `name-version/` when
+/// two versions of the same crate are locked.
+///
+/// The tree is either an unpacked directory, or a vendor tarball such as the
+/// `vendor.tar.zst` created by obs-service-cargo_vendor. Tarballs are never
+/// unpacked, the files we need are read into memory instead.
+#[derive(Debug)]
+pub enum Vendor {
+    Dir(PathBuf),
+    Archive {
+        path: PathBuf,
+        files: BTreeMap<PathBuf, Vec<u8>>,
+    },
+}
+
+/// The compression used by a vendor tarball, from its file name.
+fn archive_reader(path: &Path, file: File) -> Option<Box<dyn Read>> {
+    let name = path.file_name()?.to_string_lossy().to_lowercase();
+    let file = BufReader::new(file);
+    if name.ends_with(".tar") {
+        Some(Box::new(file))
+    } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+        Some(Box::new(flate2::read::GzDecoder::new(file)))
+    } else if name.ends_with(".tar.xz") || name.ends_with(".txz") {
+        Some(Box::new(xz2::read::XzDecoder::new(file)))
+    } else if name.ends_with(".tar.zst") || name.ends_with(".tzst") {
+        zstd::stream::read::Decoder::with_buffer(file)
+            .ok()
+            .map(|d| Box::new(d) as Box<dyn Read>)
+    } else {
+        None
+    }
+}
+
+/// Files we keep in memory when reading a tarball. Files named by a
+/// `license-file` are added afterwards.
+fn wanted(path: &Path) -> bool {
+    path.file_name().is_some_and(|n| {
+        n == "Cargo.toml"
+            || n == ".cargo-checksum.json"
+            || detect::is_license_file(&n.to_string_lossy())
+    }) || native::is_version_file(path)
+}
+
+/// Tarball entries may start with `./`, and license-file can point to a
+/// parent directory. Resolve both so paths compare cleanly.
+fn normalise(path: &Path) -> PathBuf {
+    let mut out = PathBuf::new();
+    for c in path.components() {
+        match c {
+            Component::Normal(c) => out.push(c),
+            Component::ParentDir => {
+                out.pop();
+            }
+            _ => {}
+        }
+    }
+    out
+}
+
+/// Read the wanted files of a tarball into memory, by normalised path.
+fn read_archive(
+    path: &Path,
+    wanted: &dyn Fn(&Path) -> bool,
+) -> io::Result<BTreeMap<PathBuf, Vec<u8>>> {
+    let reader = archive_reader(path, File::open(path)?).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "not a directory or a .tar, .tar.gz, .tar.xz or .tar.zst archive",
+        )
+    })?;
+
+    let mut files = BTreeMap::new();
+    let mut archive = tar::Archive::new(reader);
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let name = normalise(&entry.path()?);
+        if !wanted(&name) {
+            continue;
+        }
+        let mut buffer = Vec::with_capacity(entry.size() as usize);
+        entry.read_to_end(&mut buffer)?;
+        files.insert(name, buffer);
+    }
+    Ok(files)
+}
+
+fn strip_prefix(files: BTreeMap<PathBuf, Vec<u8>>, prefix: &Path) -> BTreeMap<PathBuf, Vec<u8>> {
+    files
+        .into_iter()
+        .filter_map(|(name, buffer)| {
+            name.strip_prefix(prefix)
+                .ok()
+                .map(|n| (n.to_path_buf(), buffer))
+        })
+        .collect()
+}
+
+impl Vendor {
+    /// Open a vendor directory or tarball.
+    pub fn open(path: PathBuf) -> io::Result<Self> {
+        Self::open_with(path, wanted)
+    }
+
+    /// Open a vendor directory or tarball, keeping every file of a tarball in
+    /// memory rather than just those needed for the licenses.
+    pub fn open_all(path: PathBuf) -> io::Result<Self> {
+        Self::open_with(path, |_| true)
+    }
+
+    fn open_with(path: PathBuf, wanted: fn(&Path) -> bool) -> io::Result<Self> {
+        if path.is_dir() {
+            return Ok(Vendor::Dir(path));
+        }
+
+        let files = read_archive(&path, &wanted)?;
+
+        // obs-service-cargo_vendor puts the crates in a top level vendor/
+        // directory. The shallowest Cargo.toml sits directly in a crate, so
+        // everything above that crate is the prefix to remove.
+        let prefix: PathBuf = files
+            .keys()
+            .filter(|p| p.ends_with("Cargo.toml"))
+            .min_by_key(|p| p.components().count())
+            .map(|p| {
+                let depth = p.components().count().saturating_sub(2);
+                p.components().take(depth).collect()
+            })
+            .unwrap_or_default();
+
+        let mut vendor = Vendor::Archive {
+            files: strip_prefix(files, &prefix),
+            path,
+        };
+
+        // A license-file can have any name, so it is only known once the
+        // Cargo.toml files are read. Fetch those in a second pass.
+        let missing: BTreeSet<PathBuf> = vendor
+            .manifest_dirs()
+            .iter()
+            .filter_map(|dir| Manifest::load(&vendor, dir).ok()?.license_file().ok()?)
+            .map(|f| normalise(&f))
+            .filter(|f| vendor.read(f).is_none())
+            .map(|f| prefix.join(f))
+            .collect();
+        if let Vendor::Archive { path, files } = &mut vendor {
+            if !missing.is_empty() {
+                let more = read_archive(path, &|p| missing.contains(p))?;
+                files.extend(strip_prefix(more, &prefix));
+            }
+        }
+
+        Ok(vendor)
+    }
+
+    /// Every directory of the tree with a Cargo.toml that was kept, including
+    /// workspace members below the top level.
+    fn manifest_dirs(&self) -> Vec<PathBuf> {
+        match self {
+            Vendor::Dir(_) => Vec::new(),
+            Vendor::Archive { files, .. } => files
+                .keys()
+                .filter(|p| p.ends_with("Cargo.toml"))
+                .filter_map(|p| p.parent())
+                .map(Path::to_path_buf)
+                .collect(),
+        }
+    }
+
+    /// An in-memory tree of files and their contents, for tests.
+    #[cfg(test)]
+    pub fn from_files(files: &[(&str, &str)]) -> Self {
+        Vendor::Archive {
+            path: PathBuf::from("vendor.tar"),
+            files: files
+                .iter()
+                .map(|(path, text)| (PathBuf::from(path), text.as_bytes().to_vec()))
+                .collect(),
+        }
+    }
+
+    /// Read a file relative to the root of the vendor tree.
+    pub fn read(&self, path: &Path) -> Option<Vec<u8>> {
+        match self {
+            Vendor::Dir(root) => std::fs::read(root.join(path)).ok(),
+            Vendor::Archive { files, .. } => files.get(&normalise(path)).cloned(),
+        }
+    }
+
+    /// The names of the files directly inside a directory of the tree. For
+    /// tarballs this only includes the files that were kept.
+    pub fn list(&self, dir: &Path) -> Vec<String> {
+        let mut names: Vec<String> = match self {
+            Vendor::Dir(root) => std::fs::read_dir(root.join(dir))
+                .map(|entries| {
+                    entries
+                        .filter_map(Result::ok)
+                        .filter(|e| e.path().is_file())
+                        .map(|e| e.file_name().to_string_lossy().into_owned())
+                        .collect()
+                })
+                .unwrap_or_default(),
+            Vendor::Archive { files, .. } => {
+                let dir = normalise(dir);
+                files
+                    .keys()
+                    .filter(|p| p.parent() == Some(dir.as_path()))
+                    .filter_map(|p| p.file_name())
+                    .map(|n| n.to_string_lossy().into_owned())
+                    .collect()
+            }
+        };
+        names.sort();
+        names
+    }
+
+    /// The full path of an entry, for messages.
+    pub fn display(&self, path: &Path) -> PathBuf {
+        match self {
+            Vendor::Dir(root) => root.join(path),
+            Vendor::Archive { path: archive, .. } => {
+                let mut name = archive.as_os_str().to_owned();
+                name.push(":");
+                name.push(path);
+                PathBuf::from(name)
+            }
+        }
+    }
+
+    /// The top level directories of the tree that contain a crate, relative
+    /// to the vendor root.
+    pub fn crate_dirs(&self) -> Vec<PathBuf> {
+        let mut dirs: Vec<PathBuf> = match self {
+            Vendor::Dir(root) => std::fs::read_dir(root)
+                .map(|entries| {
+                    entries
+                        .filter_map(Result::ok)
+                        .map(|e| PathBuf::from(e.file_name()))
+                        .filter(|d| root.join(d).join("Cargo.toml").is_file())
+                        .collect()
+                })
+                .unwrap_or_default(),
+            Vendor::Archive { files, .. } => files
+                .keys()
+                .filter(|p| p.ends_with("Cargo.toml") && p.components().count() == 2)
+                .filter_map(|p| p.parent())
+                .map(Path::to_path_buf)
+                .collect(),
+        };
+        dirs.sort();
+        dirs
+    }
+
+    /// Find the directory holding a specific version of a crate, relative to
+    /// the vendor root.
+    pub fn locate(&self, name: &str, version: &str) -> Option<PathBuf> {
+        let versioned = PathBuf::from(format!("{}-{}", name, version));
+        if self.read(&versioned.join("Cargo.toml")).is_some() {
+            return Some(versioned);
+        }
+
+        // An unversioned directory only counts if it really is this version,
+        // otherwise we'd attribute one crate's license to another.
+        let plain = PathBuf::from(name);
+        let manifest = Manifest::load(self, &plain).ok()?;
+        match manifest.version() {
+            Ok(Some(v)) => Some(plain).filter(|_| v == version),
+            // The version is inherited from a workspace that isn't vendored,
+            // or doesn't set it, so the package name is all there is to go on.
+            Err(ManifestError::NoWorkspace(..)) | Err(ManifestError::MissingInherited(..)) => {
+                Some(plain).filter(|_| manifest.name().ok().flatten().as_deref() == Some(name))
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tarball(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("{}-{}.tar", name, std::process::id()));
+        let mut builder = tar::Builder::new(File::create(&path).unwrap());
+        for (name, text) in files {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(text.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder
+                .append_data(&mut header, name, text.as_bytes())
+                .unwrap();
+        }
+        builder.finish().unwrap();
+        path
+    }
+
+    #[test]
+    fn tarballs_keep_license_file() {
+        let path = tarball(
+            "license-file",
+            &[
+                (
+                    "vendor/foo/Cargo.toml",
+                    "[package]\nname = \"foo\"\nversion = \"1.0.0\"\n\
+                     license-file = \"docs/MIT.txt\"\n",
+                ),
+                ("vendor/foo/docs/MIT.txt", "MIT License"),
+                ("vendor/foo/docs/guide.md", "guide"),
+                (
+                    "vendor/bar/Cargo.toml",
+                    "[package]\nname = \"bar\"\nversion = \"1.0.0\"\n\
+                     license-file = \"UNLICENSE\"\n",
+                ),
+                ("vendor/bar/UNLICENSE", "unlicense"),
+                ("vendor/foo/UNLICENSE", "unlicense"),
+                ("vendor/foo/src/lib.rs", ""),
+            ],
+        );
+        let vendor = Vendor::open(path.clone()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(
+            vendor.read(Path::new("foo/docs/MIT.txt")),
+            Some(b"MIT License".to_vec())
+        );
+        assert_eq!(vendor.read(Path::new("foo/docs/guide.md")), None);
+        assert_eq!(vendor.read(Path::new("foo/src/lib.rs")), None);
+        assert_eq!(vendor.list(Path::new("foo")), ["Cargo.toml"]);
+        assert_eq!(vendor.list(Path::new("bar")), ["Cargo.toml", "UNLICENSE"]);
+    }
+
+    #[test]
+    fn tarballs_keep_inherited_license_file() {
+        let path = tarball(
+            "inherited-license-file",
+            &[
+                (
+                    "./Cargo.toml",
+                    "[workspace]\nmembers = [\"foo\"]\n\n\
+                     [workspace.package]\nlicense-file = \"LICENSE.txt\"\n",
+                ),
+                (
+                    "./foo/Cargo.toml",
+                    "[package]\nname = \"foo\"\nversion = \"1.0.0\"\n\
+                     license-file.workspace = true\n",
+                ),
+                ("./LICENSE.txt", "MIT License"),
+            ],
+        );
+        let vendor = Vendor::open(path.clone()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(
+            vendor.read(Path::new("LICENSE.txt")),
+            Some(b"MIT License".to_vec())
+        );
+    }
+}
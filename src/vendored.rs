@@ -0,0 +1,357 @@
+use crate::detect;
+use crate::diagnostic::{Diagnostics, Level};
+use crate::license::LicenseExpr;
+use crate::manifest::Manifest;
+use crate::vendor::Vendor;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// Where a crate's license came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LicenseSource {
+    /// The `license` field of Cargo.toml.
+    Declared,
+    /// Classified from the text of the `license-file` in Cargo.toml.
+    File,
+    /// Classified from the LICENSE or COPYING files in the crate.
+    Detected,
+    Unknown,
+}
+
+impl fmt::Display for LicenseSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LicenseSource::Declared => write!(f, "declared"),
+            LicenseSource::File => write!(f, "file"),
+            LicenseSource::Detected => write!(f, "detected"),
+            LicenseSource::Unknown => write!(f, "unknown"),
+        }
+    }
+}
+
+/// A crate found in the vendor tree, along with what we could learn about
+/// its license.
+#[derive(Debug, Clone)]
+pub struct VendoredCrate {
+    pub name: String,
+    pub version: String,
+    /// The crate directory, relative to the vendor root.
+    pub dir: PathBuf,
+    pub license: Option<LicenseExpr>,
+    pub license_source: LicenseSource,
+}
+
+impl VendoredCrate {
+    /// Locate a locked package in the vendor tree and determine its license.
+    /// Returns None if the crate isn't vendored.
+    pub fn load(
+        vendor: &Vendor,
+        name: &str,
+        version: &str,
+        diags: &mut Diagnostics,
+    ) -> Option<Self> {
+        let dir = match vendor.locate(name, version) {
+            Some(dir) => dir,
+            None => {
+                diags.add_for(
+                    Level::Warning,
+                    name,
+                    version,
+                    format!(
+                        "is not in {:?}, check the vendor directory is up to date",
+                        vendor.display(Path::new(""))
+                    ),
+                );
+                return None;
+            }
+        };
+
+        let mut check = LicenseCheck {
+            vendor,
+            name,
+            version,
+            diags,
+        };
+        let (license, license_source) = match check.license(&dir) {
+            Some((license, source)) => (Some(license), source),
+            None => (None, LicenseSource::Unknown),
+        };
+        Some(VendoredCrate {
+            name: name.to_string(),
+            version: version.to_string(),
+            dir,
+            license,
+            license_source,
+        })
+    }
+}
+
+struct LicenseCheck<'a> {
+    vendor: &'a Vendor,
+    name: &'a str,
+    version: &'a str,
+    diags: &'a mut Diagnostics,
+}
+
+impl LicenseCheck<'_> {
+    fn report(&mut self, level: Level, message: String) {
+        self.diags.add_for(level, self.name, self.version, message);
+    }
+
+    /// Classify license texts when a crate doesn't declare an SPDX expression.
+    /// Multiple files are combined with AND, as we can't know if the author
+    /// meant a choice between them, but that is worth a warning.
+    fn detect(&mut self, files: &[PathBuf]) -> Option<LicenseExpr> {
+        let mut found = Vec::new();
+        for file in files {
+            let path = self.vendor.display(file);
+            let text = match self.vendor.read(file) {
+                Some(buffer) => String::from_utf8_lossy(&buffer).into_owned(),
+                None => {
+                    self.report(
+                        Level::Warning,
+                        format!("Unable to read license file {:?}", path),
+                    );
+                    continue;
+                }
+            };
+            match detect::classify(&text) {
+                Some(m) => {
+                    self.report(
+                        Level::Debug,
+                        format!("{:?} detected as {} ({:.2})", path, m.id, m.score),
+                    );
+                    if !m.is_confident() {
+                        self.report(
+                            Level::Warning,
+                            format!(
+                                "{:?} looks like {} with low confidence ({:.2}). You must manually review this!",
+                                path, m.id, m.score
+                            ),
+                        );
+                    }
+                    found.extend(LicenseExpr::parse(m.id).ok());
+                }
+                None => self.report(
+                    Level::Warning,
+                    format!(
+                        "Unable to identify the license in {:?}. You must manually investigate!",
+                        path
+                    ),
+                ),
+            }
+        }
+        let mut ids: Vec<String> = found.iter().map(|l| l.to_string()).collect();
+        ids.sort();
+        ids.dedup();
+        if ids.len() > 1 {
+            self.report(
+                Level::Warning,
+                format!(
+                    "{} are in separate license files and combined with AND, but this is \
+                     usually a choice. Check whether it should be {}!",
+                    ids.join(" and "),
+                    ids.join(" OR ")
+                ),
+            );
+        }
+        LicenseExpr::all(found).map(|lic| lic.simplify(&[]))
+    }
+
+    // https://doc.rust-lang.org/cargo/reference/manifest.html#the-license-and-license-file-fields
+    fn license(&mut self, crate_dir: &Path) -> Option<(LicenseExpr, LicenseSource)> {
+        let name = self.vendor.display(&crate_dir.join("Cargo.toml"));
+        self.report(Level::Debug, format!("checking license in ... {:?}", name));
+
+        let manifest = match Manifest::load(self.vendor, crate_dir) {
+            Ok(manifest) => manifest,
+            Err(e) => {
+                self.report(
+                    Level::Warning,
+                    format!(
+                        "Unable to check license from {:?} - {}. You may need to check this manually",
+                        name, e
+                    ),
+                );
+                return None;
+            }
+        };
+
+        self.report(Level::Debug, format!("Parsed config - {:?}", manifest));
+
+        let fields = manifest
+            .license()
+            .and_then(|lic| manifest.license_file().map(|file| (lic, file)));
+        let (license, license_file) = match fields {
+            Ok(fields) => fields,
+            Err(e) => {
+                self.report(
+                    Level::Warning,
+                    format!(
+                        "Unable to determine license - {}. You must manually investigate!",
+                        e
+                    ),
+                );
+                return None;
+            }
+        };
+
+        match (license, license_file) {
+            (Some(lic), _) => match LicenseExpr::parse(&lic) {
+                // Parsing canonicalises the expression, so "MIT/Apache-2.0" and
+                // "Apache-2.0 OR MIT" end up as the same license.
+                Ok(expr) => Some((expr, LicenseSource::Declared)),
+                Err(e) => {
+                    self.report(
+                        Level::Warning,
+                        format!(
+                            "Unable to parse license {:?} in {:?} - {}. You must manually investigate!",
+                            lic, name, e
+                        ),
+                    );
+                    None
+                }
+            },
+            (None, Some(file)) => self.detect(&[file]).map(|l| (l, LicenseSource::File)),
+            (None, None) => {
+                let files: Vec<PathBuf> = self
+                    .vendor
+                    .list(crate_dir)
+                    .into_iter()
+                    .filter(|f| detect::is_license_file(f))
+                    .map(|f| crate_dir.join(f))
+                    .collect();
+                if files.is_empty() {
+                    self.report(
+                        Level::Warning,
+                        format!(
+                            "Unable to determine license for {:?}. You must manually investigate!",
+                            name
+                        ),
+                    );
+                    return None;
+                }
+                self.detect(&files).map(|l| (l, LicenseSource::Detected))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INHERITING: &str = r#"
+[package]
+name = "foo"
+version.workspace = true
+license.workspace = true
+"#;
+
+    #[test]
+    fn inherited_fields_resolve_from_a_vendored_workspace() {
+        let vendor = Vendor::from_files(&[
+            ("foo/Cargo.toml", INHERITING),
+            (
+                "Cargo.toml",
+                r#"
+[workspace]
+members = ["foo"]
+
+[workspace.package]
+version = "1.0.0"
+license = "MIT OR Apache-2.0"
+"#,
+            ),
+        ]);
+        let mut diags = Diagnostics::new(false);
+        let krate = VendoredCrate::load(&vendor, "foo", "1.0.0", &mut diags).unwrap();
+        assert_eq!(krate.dir, PathBuf::from("foo"));
+        assert_eq!(krate.license.unwrap().to_string(), "Apache-2.0 OR MIT");
+        assert_eq!(krate.license_source, LicenseSource::Declared);
+    }
+
+    #[test]
+    fn inherited_fields_without_the_workspace_are_reported() {
+        let vendor = Vendor::from_files(&[("foo/Cargo.toml", INHERITING)]);
+        let mut diags = Diagnostics::new(false);
+        let krate = VendoredCrate::load(&vendor, "foo", "1.0.0", &mut diags).unwrap();
+        assert_eq!(krate.dir, PathBuf::from("foo"));
+        assert!(krate.license.is_none());
+
+        let messages: Vec<String> = diags.into_vec().into_iter().map(|d| d.message).collect();
+        assert!(
+            messages
+                .iter()
+                .any(|m| m.contains("inherits license from a workspace that isn't vendored")),
+            "{:?}",
+            messages
+        );
+        assert!(!messages.iter().any(|m| m.contains("is not in")));
+    }
+
+    #[test]
+    fn inherited_fields_missing_from_the_workspace_are_reported() {
+        let vendor = Vendor::from_files(&[
+            ("foo/Cargo.toml", INHERITING),
+            (
+                "Cargo.toml",
+                "[workspace]\nmembers = [\"foo\"]\n\n[workspace.package]\nversion = \"1.0.0\"\n",
+            ),
+        ]);
+        let mut diags = Diagnostics::new(false);
+        let krate = VendoredCrate::load(&vendor, "foo", "1.0.0", &mut diags).unwrap();
+        assert!(krate.license.is_none());
+
+        let messages: Vec<String> = diags.into_vec().into_iter().map(|d| d.message).collect();
+        assert!(
+            messages
+                .iter()
+                .any(|m| m.contains("inherits license, but workspace.package.license is missing")),
+            "{:?}",
+            messages
+        );
+    }
+
+    #[test]
+    fn inherited_version_needs_a_matching_name() {
+        let vendor = Vendor::from_files(&[
+            ("foo/Cargo.toml", INHERITING),
+            ("bar/Cargo.toml", INHERITING),
+        ]);
+        assert_eq!(vendor.locate("foo", "1.0.0"), Some(PathBuf::from("foo")));
+        assert_eq!(vendor.locate("bar", "1.0.0"), None);
+    }
+
+    #[test]
+    fn separate_license_files_may_be_a_choice() {
+        let vendor = Vendor::from_files(&[
+            (
+                "dual/Cargo.toml",
+                "[package]\nname = \"dual\"\nversion = \"1.0.0\"\n",
+            ),
+            (
+                "dual/LICENSE-APACHE",
+                include_str!("licenses/Apache-2.0.txt"),
+            ),
+            ("dual/LICENSE-MIT", include_str!("licenses/MIT.txt")),
+        ]);
+        let mut diags = Diagnostics::new(false);
+        let krate = VendoredCrate::load(&vendor, "dual", "1.0.0", &mut diags).unwrap();
+        assert_eq!(krate.license.unwrap().to_string(), "Apache-2.0 AND MIT");
+        assert_eq!(krate.license_source, LicenseSource::Detected);
+        let warnings: Vec<String> = diags
+            .into_vec()
+            .into_iter()
+            .filter(|d| d.level == Level::Warning)
+            .map(|d| d.message)
+            .collect();
+        assert_eq!(
+            warnings,
+            [
+                "Apache-2.0 and MIT are in separate license files and combined with AND, but this \
+              is usually a choice. Check whether it should be Apache-2.0 OR MIT!"
+            ]
+        );
+    }
+}
@@ -0,0 +1,223 @@
+//! Verify vendored crates against the checksums in Cargo.lock and the
+//! `.cargo-checksum.json` that `cargo vendor` writes into each crate.
+
+use crate::lockfile::LockedPackage;
+use crate::vendor::Vendor;
+use serde_derive::Deserialize;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Problem {
+    /// The crate has no `.cargo-checksum.json`.
+    NoChecksumFile,
+    InvalidChecksumFile(String),
+    /// The vendored crate isn't the one that was locked.
+    PackageMismatch {
+        locked: String,
+        vendored: String,
+    },
+    /// The vendored crate has no package checksum, but the lockfile does.
+    NoPackageChecksum,
+    /// A file listed in `.cargo-checksum.json` is missing.
+    FileMissing(String),
+    /// A file was modified after the crate was vendored.
+    FileMismatch(String),
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Problem::NoChecksumFile => write!(f, ".cargo-checksum.json is missing"),
+            Problem::InvalidChecksumFile(e) => {
+                write!(f, ".cargo-checksum.json could not be parsed - {}", e)
+            }
+            Problem::PackageMismatch { locked, vendored } => write!(
+                f,
+                "checksum {} does not match {} in Cargo.lock, the vendor tree is stale",
+                vendored, locked
+            ),
+            Problem::NoPackageChecksum => write!(
+                f,
+                ".cargo-checksum.json has no package checksum, but Cargo.lock does"
+            ),
+            Problem::FileMissing(file) => write!(f, "{} is missing", file),
+            Problem::FileMismatch(file) => {
+                write!(f, "{} does not match .cargo-checksum.json", file)
+            }
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct Checksums {
+    files: BTreeMap<String, String>,
+    package: Option<String>,
+}
+
+fn sha256(buffer: &[u8]) -> String {
+    Sha256::digest(buffer)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Check a vendored crate in `dir`, relative to the vendor root. If `files`
+/// is set every file is hashed too, which for tarballs needs a vendor opened
+/// with `Vendor::open_all`.
+pub fn verify(vendor: &Vendor, pkg: &LockedPackage, dir: &Path, files: bool) -> Vec<Problem> {
+    let buffer = match vendor.read(&dir.join(".cargo-checksum.json")) {
+        Some(buffer) => buffer,
+        None => return vec![Problem::NoChecksumFile],
+    };
+    let checksums: Checksums = match serde_json::from_slice(&buffer) {
+        Ok(checksums) => checksums,
+        Err(e) => return vec![Problem::InvalidChecksumFile(e.to_string())],
+    };
+
+    let mut problems = Vec::new();
+    match (&pkg.checksum, &checksums.package) {
+        (Some(locked), Some(vendored)) if !locked.eq_ignore_ascii_case(vendored) => {
+            problems.push(Problem::PackageMismatch {
+                locked: locked.clone(),
+                vendored: vendored.clone(),
+            })
+        }
+        (Some(_), None) => problems.push(Problem::NoPackageChecksum),
+        _ => {}
+    }
+
+    if files {
+        for (file, expected) in &checksums.files {
+            match vendor.read(&dir.join(file)) {
+                Some(buffer) if sha256(&buffer).eq_ignore_ascii_case(expected) => {}
+                Some(_) => problems.push(Problem::FileMismatch(file.clone())),
+                None => problems.push(Problem::FileMissing(file.clone())),
+            }
+        }
+    }
+    problems
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lockfile::LockFile;
+
+    const LOCKED: &str = "49f1f14873335454500d59611f1cf4a4b0f786f9ac11f4312a78e4cf2566695b";
+    const LIB: &str = "pub fn itoa() {}\n";
+
+    fn lock() -> LockFile {
+        LockFile::parse(
+            format!(
+                r#"
+version = 4
+
+[[package]]
+name = "itoa"
+version = "1.0.11"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+checksum = "{}"
+
+[[package]]
+name = "gitdep"
+version = "0.1.0"
+source = "git+https://example.com/gitdep.git#0123456789abcdef0123456789abcdef01234567"
+"#,
+                LOCKED
+            )
+            .as_bytes(),
+        )
+        .unwrap()
+    }
+
+    fn checksums(package: Option<&str>, lib: &str) -> String {
+        format!(
+            r#"{{"files": {{"src/lib.rs": "{}"}}, "package": {}}}"#,
+            lib,
+            package.map_or("null".to_string(), |p| format!("{:?}", p))
+        )
+    }
+
+    fn check(files: &[(&str, &str)], package: &str, all_files: bool) -> Vec<Problem> {
+        let vendor = Vendor::from_files(files);
+        let lock = lock();
+        let pkg = lock.packages.iter().find(|p| p.name == package).unwrap();
+        verify(&vendor, pkg, Path::new(package), all_files)
+    }
+
+    #[test]
+    fn matching_crate() {
+        let json = checksums(Some(LOCKED), &sha256(LIB.as_bytes()));
+        let files = [
+            ("itoa/.cargo-checksum.json", json.as_str()),
+            ("itoa/src/lib.rs", LIB),
+        ];
+        assert!(check(&files, "itoa", true).is_empty());
+    }
+
+    #[test]
+    fn stale_package() {
+        let json = checksums(Some(&"0".repeat(64)), &sha256(LIB.as_bytes()));
+        let files = [
+            ("itoa/.cargo-checksum.json", json.as_str()),
+            ("itoa/src/lib.rs", LIB),
+        ];
+        assert_eq!(
+            check(&files, "itoa", false),
+            [Problem::PackageMismatch {
+                locked: LOCKED.to_string(),
+                vendored: "0".repeat(64),
+            }]
+        );
+
+        let json = checksums(None, &sha256(LIB.as_bytes()));
+        let files = [("itoa/.cargo-checksum.json", json.as_str())];
+        assert_eq!(check(&files, "itoa", false), [Problem::NoPackageChecksum]);
+    }
+
+    #[test]
+    fn checksum_file() {
+        assert_eq!(
+            check(&[("itoa/src/lib.rs", LIB)], "itoa", false),
+            [Problem::NoChecksumFile]
+        );
+        assert!(matches!(
+            check(&[("itoa/.cargo-checksum.json", "{")], "itoa", false).as_slice(),
+            [Problem::InvalidChecksumFile(_)]
+        ));
+    }
+
+    #[test]
+    fn modified_files() {
+        let json = checksums(Some(LOCKED), &sha256(LIB.as_bytes()));
+        let tampered = [
+            ("itoa/.cargo-checksum.json", json.as_str()),
+            ("itoa/src/lib.rs", "pub fn itoa() { evil() }\n"),
+        ];
+        assert_eq!(
+            check(&tampered, "itoa", true),
+            [Problem::FileMismatch("src/lib.rs".to_string())]
+        );
+        // Files are only hashed when asked for.
+        assert!(check(&tampered, "itoa", false).is_empty());
+
+        let missing = [("itoa/.cargo-checksum.json", json.as_str())];
+        assert_eq!(
+            check(&missing, "itoa", true),
+            [Problem::FileMissing("src/lib.rs".to_string())]
+        );
+    }
+
+    #[test]
+    fn git_dependencies_have_no_package_checksum() {
+        let json = checksums(None, &sha256(LIB.as_bytes()));
+        let files = [
+            ("gitdep/.cargo-checksum.json", json.as_str()),
+            ("gitdep/src/lib.rs", LIB),
+        ];
+        assert!(check(&files, "gitdep", true).is_empty());
+    }
+}
@@ -0,0 +1,126 @@
+use std::path::{Path, PathBuf};
+
+/// A package that is built as part of the workspace, rather than bundled.
+#[derive(Debug, Clone)]
+pub struct Member {
+    pub name: String,
+    pub path: PathBuf,
+}
+
+/// The packages described by the Cargo.toml in the working directory.
+///
+/// This is either a single package, a virtual workspace, or a package that
+/// is also a workspace root.
+#[derive(Debug)]
+pub struct Workspace {
+    pub members: Vec<Member>,
+}
+
+fn read_manifest(path: &Path) -> Option<toml::Value> {
+    let buffer = std::fs::read(path.join("Cargo.toml")).ok()?;
+    toml::from_slice(&buffer).ok()
+}
+
+fn package_name(manifest: &toml::Value) -> Option<String> {
+    manifest
+        .get("package")
+        .and_then(|p| p.get("name"))
+        .and_then(|n| n.as_str())
+        .map(str::to_string)
+}
+
+fn string_list(table: &toml::Value, key: &str) -> Vec<String> {
+    table
+        .get(key)
+        .and_then(|v| v.as_array())
+        .map(|a| {
+            a.iter()
+                .filter_map(|v| v.as_str())
+                .map(str::to_string)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Match a single path component against a pattern containing `*` and `?`.
+fn glob_match(pattern: &[u8], name: &[u8]) -> bool {
+    match (pattern.first(), name.first()) {
+        (None, None) => true,
+        (Some(b'*'), _) => {
+            glob_match(&pattern[1..], name) || (!name.is_empty() && glob_match(pattern, &name[1..]))
+        }
+        (Some(b'?'), Some(_)) => glob_match(&pattern[1..], &name[1..]),
+        (Some(p), Some(n)) if p == n => glob_match(&pattern[1..], &name[1..]),
+        _ => false,
+    }
+}
+
+/// Expand a workspace member glob such as `crates/*` relative to root.
+fn expand_member(root: &Path, pattern: &str) -> Vec<PathBuf> {
+    let mut paths = vec![root.to_path_buf()];
+    for component in Path::new(pattern).components() {
+        let component = component.as_os_str().to_string_lossy();
+        if !component.contains('*') && !component.contains('?') {
+            paths = paths
+                .into_iter()
+                .map(|p| p.join(component.as_ref()))
+                .collect();
+            continue;
+        }
+        paths = paths
+            .into_iter()
+            .filter_map(|p| std::fs::read_dir(p).ok())
+            .flat_map(|entries| entries.filter_map(Result::ok))
+            .filter(|e| e.path().is_dir())
+            .filter(|e| {
+                glob_match(
+                    component.as_bytes(),
+                    e.file_name().to_string_lossy().as_bytes(),
+                )
+            })
+            .map(|e| e.path())
+            .collect();
+        paths.sort();
+    }
+    paths
+}
+
+impl Workspace {
+    /// Load the workspace rooted at the directory containing Cargo.toml.
+    /// Returns None if there is no readable manifest.
+    pub fn load(root: &Path) -> Option<Self> {
+        let manifest = read_manifest(root)?;
+        let mut members = Vec::new();
+
+        if let Some(name) = package_name(&manifest) {
+            members.push(Member {
+                name,
+                path: root.to_path_buf(),
+            });
+        }
+
+        if let Some(ws) = manifest.get("workspace") {
+            let exclude: Vec<PathBuf> = string_list(ws, "exclude")
+                .iter()
+                .map(|e| root.join(e))
+                .collect();
+
+            for pattern in string_list(ws, "members") {
+                for path in expand_member(root, &pattern) {
+                    if exclude.iter().any(|e| path.starts_with(e)) || path == root {
+                        continue;
+                    }
+                    if let Some(name) = read_manifest(&path).as_ref().and_then(package_name) {
+                        members.push(Member { name, path });
+                    }
+                }
+            }
+        }
+
+        Some(Workspace { members })
+    }
+
+    pub fn is_member(&self, name: &str) -> bool {
+        self.members.iter().any(|m| m.name == name)
+    }
+}